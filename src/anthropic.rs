@@ -1,34 +1,82 @@
-use crate::client::{CommitMessageGenerator, PullRequestGenerator};
+use crate::client::{
+    append_feedback, apply_prefix, build_pr_context, build_tag_prompt, build_template_prompt,
+    check_model_response, parse_model_list, Answer, CommitMessageGenerator, Feedback, Instructions,
+    PullRequestGenerator, BUMP_INSTRUCTIONS, CLASSIFY_INSTRUCTIONS, EXPLAIN_INSTRUCTIONS,
+    SPLIT_INSTRUCTIONS, SQUASH_INSTRUCTIONS,
+};
 use crate::config::ServiceConfig;
 use reqwest::blocking::Client;
 use serde_json::{json, Value};
 use std::error::Error;
 
+static ANTHROPIC_VERSION: &str = "2023-06-01";
+
 pub struct AnthropicClient {
     api_token: String,
     client: Client,
     model: String,
+    instructions: Instructions,
 }
 
 impl AnthropicClient {
-    pub fn new(service_config: &ServiceConfig) -> Self {
-        AnthropicClient {
-            api_token: service_config.api_token.clone(),
+    pub fn new(service_config: &ServiceConfig) -> Result<Self, std::io::Error> {
+        Ok(AnthropicClient {
+            api_token: service_config.api_token.resolve()?,
             model: service_config.model.clone(),
             client: Client::new(),
-        }
+            instructions: Instructions::default(),
+        })
+    }
+
+    /// Sets extra instructions that are sent with the requests they apply to.
+    pub fn with_instructions(mut self, instructions: Instructions) -> Self {
+        self.instructions = instructions;
+        self
+    }
+
+    /// Checks that the API key is valid and the configured model exists.
+    pub fn check_model(&self) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .get(format!(
+                "https://api.anthropic.com/v1/models/{}",
+                self.model
+            ))
+            .header("x-api-key", &self.api_token)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .send()?;
+
+        check_model_response(response.status(), &self.model)
+    }
+
+    /// Lists the models the API key has access to.
+    pub fn list_models(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let response = self
+            .client
+            .get("https://api.anthropic.com/v1/models?limit=1000")
+            .header("x-api-key", &self.api_token)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .send()?;
+
+        parse_model_list(response.status(), &response.json()?)
     }
 
-    fn generate_message(&self, messages: &Value) -> Result<String, Box<dyn Error>> {
+    fn generate_message(&self, messages: &Value, answer: Answer) -> Result<String, Box<dyn Error>> {
+        let mut body = json!({
+            "model": &self.model,
+            "messages": &messages,
+            "max_tokens": 1024,
+        });
+        if let Some(prompt) = self.instructions.system_prompt(answer) {
+            body["system"] = json!(prompt);
+        }
+
         let response = self
             .client
             .post("https://api.anthropic.com/v1/messages")
             .header("x-api-key", &self.api_token)
-            .json(&json!({
-                "model": &self.model,
-                "messages": &messages,
-                "max_tokens": 1024,
-            }))
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&body)
             .send()?;
 
         let response_json: Value = response.json()?;
@@ -40,63 +88,210 @@ impl AnthropicClient {
             .and_then(|text| text.as_str())
             .map(|text| text.to_string())
             .ok_or_else(|| {
-                Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!(
-                        "Failed to generate commit message. Unexpected response format: {}",
-                        response_json
-                    ),
-                )) as Box<dyn Error>
+                Box::new(std::io::Error::other(format!(
+                    "Failed to generate message. Unexpected response format: {}",
+                    response_json
+                ))) as Box<dyn Error>
             })
     }
 }
 
-#[allow(dead_code)]
 impl PullRequestGenerator for AnthropicClient {
     fn generate_pr_title(
         &self,
-        _diff: &str,
-        _prefix: Option<&String>,
+        diff: &str,
+        commits: &str,
+        prefix: Option<&String>,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn Error>> {
+        let mut messages = json!([
+            {
+                "role": "user",
+                "content": format!(
+                    "{}\n\nGenerate a concise and meaningful title for a GitHub pull request based on the provided commit messages and git diff.\n\
+                    Only answer with the title, nothing else.",
+                    build_pr_context(diff, commits)
+                )
+            }
+        ]);
+
+        append_feedback(&mut messages, feedback);
+
+        let title = self.generate_message(&messages, Answer::Message)?;
+
+        Ok(apply_prefix(&title, prefix))
+    }
+
+    fn generate_pr_description(
+        &self,
+        diff: &str,
+        commits: &str,
+        template: Option<&str>,
+        feedback: &[Feedback],
     ) -> Result<String, Box<dyn Error>> {
-        todo!()
+        let mut messages = json!([
+            {
+                "role": "user",
+                "content": format!(
+                    "{}\n\nGenerate a detailed and meaningful description for a GitHub pull request based on the provided commit messages and git diff.\n\
+                    Use the commit messages to understand the intent behind the changes.\n\
+                    Only answer with the description, nothing else. The description will be read by engineers, so keep it concise and meaningful.\n\
+                    Don't include any How To's or comments about future work.\n\
+                    ONLY include the changes made.{}",
+                    build_pr_context(diff, commits),
+                    template
+                        .map(|template| format!("\n\n{}", build_template_prompt(template)))
+                        .unwrap_or_default()
+                )
+            }
+        ]);
+
+        append_feedback(&mut messages, feedback);
+
+        let description = self.generate_message(&messages, Answer::Other)?;
+
+        Ok(description.trim().to_string())
     }
 
-    #[allow(dead_code)]
-    fn generate_pr_description(&self, _diff: &str) -> Result<String, Box<dyn Error>> {
-        todo!()
+    fn explain_changes(&self, diff: &str, commits: &str) -> Result<String, Box<dyn Error>> {
+        let messages = json!([
+            {
+                "role": "user",
+                "content": format!("{}\n\n{}", build_pr_context(diff, commits), EXPLAIN_INSTRUCTIONS)
+            }
+        ]);
+
+        let explanation = self.generate_message(&messages, Answer::Other)?;
+
+        Ok(explanation.trim().to_string())
     }
 }
 
 impl CommitMessageGenerator for AnthropicClient {
-    fn generate_commit_message(
+    fn generate_commit_messages(
         &self,
         diff: &str,
         prefix: Option<&String>,
+        count: usize,
+        feedback: &[Feedback],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        // Anthropic takes no system messages in the conversation, and turns have to
+        // alternate so the feedback can follow
+        let mut messages = json!([
+            {
+                "role": "user",
+                "content": format!(
+                    "You are a helpful assistant specialized in writing concise and meaningful git commit messages.\n\n\
+                    Here is a git diff:\n\n{}\n\n\
+                    Generate a concise and meaningful commit message based on the provided git diff.\n\
+                    Only include the concise and meaningful commit message. Don't include any text formatting.",
+                    diff
+                )
+            }
+        ]);
+
+        append_feedback(&mut messages, feedback);
+
+        // The API has no option for alternatives, so send the requests in parallel
+        let results: Vec<Result<String, String>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..count)
+                .map(|_| {
+                    scope.spawn(|| {
+                        self.generate_message(&messages, Answer::Message)
+                            .map_err(|e| e.to_string())
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("request thread panicked"))
+                .collect()
+        });
+
+        results
+            .into_iter()
+            .map(|message| Ok(apply_prefix(&message?, prefix)))
+            .collect()
+    }
+
+    fn generate_squash_message(
+        &self,
+        diff: &str,
+        commits: &str,
+        prefix: Option<&String>,
+        feedback: &[Feedback],
     ) -> Result<String, Box<dyn Error>> {
+        let mut messages = json!([
+            {
+                "role": "user",
+                "content": format!("{}\n\n{}", build_pr_context(diff, commits), SQUASH_INSTRUCTIONS)
+            }
+        ]);
+
+        append_feedback(&mut messages, feedback);
+
+        let message = self.generate_message(&messages, Answer::Message)?;
+
+        Ok(apply_prefix(&message, prefix))
+    }
+
+    fn generate_commit_plan(
+        &self,
+        hunks: &str,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn Error>> {
+        let mut messages = json!([
+            {
+                "role": "user",
+                "content": format!("Here are the staged hunks:\n\n{}\n\n{}", hunks, SPLIT_INSTRUCTIONS)
+            }
+        ]);
+
+        append_feedback(&mut messages, feedback);
+
+        self.generate_message(&messages, Answer::Message)
+    }
+
+    fn classify_changes(&self, changes: &str) -> Result<String, Box<dyn Error>> {
         let messages = json!([
             {
-                "role": "system",
-                "content": "You are a helpful assistant specialized in writing concise and meaningful git commit messages."
-            },
+                "role": "user",
+                "content": format!("Here are the changes:\n\n{}\n\n{}", changes, CLASSIFY_INSTRUCTIONS)
+            }
+        ]);
+
+        self.generate_message(&messages, Answer::Other)
+    }
+
+    fn suggest_bump(&self, diff: &str, commits: &str) -> Result<String, Box<dyn Error>> {
+        let messages = json!([
             {
                 "role": "user",
-                "content": format!("Here is a git diff:\n\n{}", diff)
-            },
+                "content": format!("{}\n\n{}", build_pr_context(diff, commits), BUMP_INSTRUCTIONS)
+            }
+        ]);
+
+        self.generate_message(&messages, Answer::Other)
+    }
+
+    fn generate_tag_message(
+        &self,
+        diff: &str,
+        commits: &str,
+        version: &str,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn Error>> {
+        let mut messages = json!([
             {
-                "role": "assistant",
-                "content": "Generate a concise and meaningful commit message based on the provided git diff.\n\
-                Only include the concise and meaningful commit message. Don't include any text formatting."
+                "role": "user",
+                "content": format!("{}\n\n{}", build_pr_context(diff, commits), build_tag_prompt(version))
             }
         ]);
 
-        let message = self.generate_message(&messages)?;
+        append_feedback(&mut messages, feedback);
 
-        let final_message = if let Some(prefix) = prefix {
-            format!("{} {}", prefix, message.trim())
-        } else {
-            message.trim().to_string()
-        };
+        let message = self.generate_message(&messages, Answer::Other)?;
 
-        Ok(final_message)
+        Ok(message.trim().to_string())
     }
 }
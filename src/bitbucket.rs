@@ -0,0 +1,205 @@
+use crate::config::ForgeConfig;
+use crate::forge::{PullRequestOptions, PullRequestTarget};
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+use std::error::Error;
+
+/// Creates pull requests through the Bitbucket Server/Data Center REST API.
+pub struct BitbucketClient {
+    api_token: String,
+    base_url: String,
+    client: Client,
+}
+
+impl BitbucketClient {
+    pub fn new(forge_config: &ForgeConfig) -> Result<Self, std::io::Error> {
+        Ok(BitbucketClient {
+            api_token: forge_config.api_token.resolve()?,
+            base_url: forge_config.base_url.trim_end_matches('/').to_string(),
+            client: Client::new(),
+        })
+    }
+
+    /// Creates a pull request and returns its URL.
+    pub fn create_pull_request(
+        &self,
+        target: &PullRequestTarget,
+        title: &str,
+        description: &str,
+        options: &PullRequestOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        if !options.labels.is_empty()
+            || !options.assignees.is_empty()
+            || options.milestone.is_some()
+        {
+            eprintln!("Warning: Bitbucket does not support labels, assignees or milestones, ignoring them.");
+        }
+
+        let project = project_key(&target.owner);
+        let repository = json!({
+            "slug": target.repo,
+            "project": { "key": project },
+        });
+        let reviewers: Vec<Value> = options
+            .reviewers
+            .iter()
+            .map(|reviewer| json!({ "user": { "name": reviewer } }))
+            .collect();
+
+        let url = format!(
+            "{}/rest/api/1.0/projects/{}/repos/{}/pull-requests",
+            self.base_url, project, target.repo
+        );
+        let response = self
+            .client
+            .post(&url)
+            .bearer_auth(&self.api_token)
+            .json(&json!({
+                "title": title,
+                "description": description,
+                "draft": options.draft,
+                "fromRef": {
+                    "id": format!("refs/heads/{}", target.head),
+                    "repository": repository,
+                },
+                "toRef": {
+                    "id": format!("refs/heads/{}", target.base),
+                    "repository": repository,
+                },
+                "reviewers": reviewers,
+            }))
+            .send()?;
+
+        let status = response.status();
+        let response_json: Value = response.json().unwrap_or(Value::Null);
+
+        if !status.is_success() {
+            return Err(Box::new(std::io::Error::other(format!(
+                "Bitbucket API request to {} failed with {}: {}",
+                url, status, response_json
+            ))));
+        }
+
+        response_json
+            .get("links")
+            .and_then(|links| links.get("self"))
+            .and_then(|links| links.get(0))
+            .and_then(|link| link.get("href"))
+            .and_then(Value::as_str)
+            .map(|url| url.to_string())
+            .ok_or_else(|| {
+                Box::new(std::io::Error::other(format!(
+                    "Failed to create pull request. Unexpected response format: {}",
+                    response_json
+                ))) as Box<dyn Error>
+            })
+    }
+}
+
+/// Bitbucket project keys are upper case, but clone URLs often use lower case.
+/// Personal repositories are under `~user`, which keeps the user's case.
+fn project_key(owner: &str) -> String {
+    if owner.starts_with('~') {
+        owner.to_string()
+    } else {
+        owner.to_uppercase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiToken, ForgeKind};
+    use crate::test_server::StubServer;
+
+    fn client_for(server: &StubServer) -> BitbucketClient {
+        BitbucketClient::new(&ForgeConfig {
+            kind: ForgeKind::Bitbucket,
+            base_url: server.base_url.clone(),
+            api_token: ApiToken::Plain("secret".to_string()),
+        })
+        .unwrap()
+    }
+
+    fn target() -> PullRequestTarget {
+        PullRequestTarget {
+            owner: "proj".to_string(),
+            repo: "repo".to_string(),
+            head: "feature".to_string(),
+            base: "main".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_create_pull_request_posts_refs_and_returns_url() {
+        let server = StubServer::start(vec![(
+            "POST /rest/api/1.0/projects/PROJ/repos/repo/pull-requests",
+            201,
+            r#"{"id": 3, "links": {"self": [{"href": "https://bitbucket.example/projects/PROJ/repos/repo/pull-requests/3"}]}}"#,
+        )]);
+        let options = PullRequestOptions {
+            draft: true,
+            reviewers: vec!["alice".to_string()],
+            ..Default::default()
+        };
+
+        let url = client_for(&server)
+            .create_pull_request(&target(), "Add feature", "Description", &options)
+            .unwrap();
+
+        assert_eq!(
+            url,
+            "https://bitbucket.example/projects/PROJ/repos/repo/pull-requests/3"
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests[0].headers["authorization"], "Bearer secret");
+        let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body["fromRef"]["id"], "refs/heads/feature");
+        assert_eq!(body["toRef"]["id"], "refs/heads/main");
+        assert_eq!(body["toRef"]["repository"]["project"]["key"], "PROJ");
+        assert_eq!(body["draft"], true);
+        assert_eq!(body["reviewers"], json!([{ "user": { "name": "alice" } }]));
+    }
+
+    #[test]
+    fn test_create_pull_request_keeps_personal_project_keys() {
+        let server = StubServer::start(vec![(
+            "POST /rest/api/1.0/projects/~alice/repos/repo/pull-requests",
+            201,
+            r#"{"id": 3, "links": {"self": [{"href": "https://bitbucket.example/users/alice/repos/repo/pull-requests/3"}]}}"#,
+        )]);
+        let target = PullRequestTarget {
+            owner: "~alice".to_string(),
+            ..target()
+        };
+
+        client_for(&server)
+            .create_pull_request(&target, "Add feature", "", &PullRequestOptions::default())
+            .unwrap();
+
+        let body: Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+        assert_eq!(body["toRef"]["repository"]["project"]["key"], "~alice");
+    }
+
+    #[test]
+    fn test_create_pull_request_returns_error_on_failure() {
+        let server = StubServer::start(vec![(
+            "POST /rest/api/1.0/projects/PROJ/repos/repo/pull-requests",
+            401,
+            r#"{"errors": [{"message": "Authentication failed"}]}"#,
+        )]);
+
+        let result = client_for(&server).create_pull_request(
+            &target(),
+            "Add feature",
+            "",
+            &PullRequestOptions::default(),
+        );
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Authentication failed"));
+    }
+}
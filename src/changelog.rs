@@ -0,0 +1,221 @@
+const HEADER: &str = "# Changelog
+
+All notable changes to this project will be documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/),
+and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).
+";
+
+/// The sections of a Keep a Changelog entry, in the order they are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Added,
+    Changed,
+    Deprecated,
+    Removed,
+    Fixed,
+    Security,
+}
+
+impl Section {
+    pub const ALL: [Section; 6] = [
+        Section::Added,
+        Section::Changed,
+        Section::Deprecated,
+        Section::Removed,
+        Section::Fixed,
+        Section::Security,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Section::Added => "Added",
+            Section::Changed => "Changed",
+            Section::Deprecated => "Deprecated",
+            Section::Removed => "Removed",
+            Section::Fixed => "Fixed",
+            Section::Security => "Security",
+        }
+    }
+
+    /// Reads a section name as the model answers it. `Internal` and anything
+    /// unknown leave the change out.
+    pub fn from_name(name: &str) -> Option<Section> {
+        Section::ALL
+            .into_iter()
+            .find(|section| section.title().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Reads a conventional commit subject, `type(scope)!: description`. Returns
+/// the section for its type, `None` for types that stay out of release notes,
+/// and the description. Subjects that aren't conventional return `None`.
+pub fn parse_conventional(subject: &str) -> Option<(Option<Section>, &str)> {
+    let (kind, description) = subject.split_once(": ")?;
+    let kind = kind.trim_end_matches('!');
+    let kind = match kind.split_once('(') {
+        Some((kind, scope)) if scope.ends_with(')') => kind,
+        Some(_) => return None,
+        None => kind,
+    };
+
+    let section = match kind.to_ascii_lowercase().as_str() {
+        "feat" | "feature" | "add" => Some(Section::Added),
+        "fix" | "bugfix" | "hotfix" => Some(Section::Fixed),
+        "perf" | "refactor" | "revert" | "change" => Some(Section::Changed),
+        "deprecate" => Some(Section::Deprecated),
+        "remove" => Some(Section::Removed),
+        "security" | "sec" => Some(Section::Security),
+        "docs" | "style" | "test" | "tests" | "build" | "ci" | "chore" => None,
+        _ => return None,
+    };
+
+    Some((section, description.trim()))
+}
+
+/// Writes a release entry with the changes under their section headings.
+pub fn render_entry(heading: &str, changes: &[(Section, String)]) -> String {
+    let mut entry = format!("## {}\n", heading);
+
+    for section in Section::ALL {
+        let items: Vec<&String> = changes
+            .iter()
+            .filter(|(change_section, _)| *change_section == section)
+            .map(|(_, change)| change)
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+
+        entry.push_str(&format!("\n### {}\n\n", section.title()));
+        for item in items {
+            entry.push_str(&format!("- {}\n", item));
+        }
+    }
+
+    entry
+}
+
+fn is_unreleased(line: &str) -> bool {
+    line.trim().eq_ignore_ascii_case("## [Unreleased]")
+}
+
+/// Adds an entry above the previous releases, creating the changelog if there
+/// is none. An Unreleased entry replaces the existing one, and a release goes
+/// below it.
+pub fn prepend_entry(changelog: Option<&str>, entry: &str) -> String {
+    let changelog = match changelog {
+        Some(changelog) if !changelog.trim().is_empty() => changelog,
+        _ => return format!("{}\n{}", HEADER, entry),
+    };
+
+    let mut start = None;
+    let mut end = None;
+    let mut offset = 0;
+    for line in changelog.split_inclusive('\n') {
+        if line.starts_with("## ") {
+            if is_unreleased(line) && start.is_none() {
+                start = Some(offset);
+            } else {
+                end = Some(offset);
+                break;
+            }
+        }
+        offset += line.len();
+    }
+
+    let replace_unreleased = entry.lines().next().is_some_and(is_unreleased);
+    let (before, after) = match (start, end) {
+        (Some(start), end) if replace_unreleased => (
+            &changelog[..start],
+            &changelog[end.unwrap_or(changelog.len())..],
+        ),
+        (_, Some(end)) => (&changelog[..end], &changelog[end..]),
+        (_, None) => (changelog, ""),
+    };
+
+    let mut result = before.to_string();
+    if !result.is_empty() && !result.ends_with("\n\n") {
+        result.push_str(if result.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+    result.push_str(entry);
+    if !after.is_empty() {
+        result.push('\n');
+        result.push_str(after);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_conventional() {
+        assert_eq!(
+            parse_conventional("feat(cli): add --all"),
+            Some((Some(Section::Added), "add --all"))
+        );
+        assert_eq!(
+            parse_conventional("fix!: keep staged files"),
+            Some((Some(Section::Fixed), "keep staged files"))
+        );
+        assert_eq!(
+            parse_conventional("chore: bump deps"),
+            Some((None, "bump deps"))
+        );
+        assert_eq!(parse_conventional("Add --all to commit"), None);
+        assert_eq!(parse_conventional("Note: this is not a type"), None);
+    }
+
+    #[test]
+    fn test_render_entry_orders_sections() {
+        let changes = vec![
+            (Section::Fixed, "Keep staged files (#12)".to_string()),
+            (Section::Added, "Add --all".to_string()),
+            (Section::Fixed, "Handle empty diffs".to_string()),
+        ];
+
+        assert_eq!(
+            render_entry("[1.2.0] - 2024-05-01", &changes),
+            "## [1.2.0] - 2024-05-01\n\n\
+             ### Added\n\n\
+             - Add --all\n\n\
+             ### Fixed\n\n\
+             - Keep staged files (#12)\n\
+             - Handle empty diffs\n"
+        );
+    }
+
+    #[test]
+    fn test_prepend_entry_creates_the_changelog() {
+        let changelog = prepend_entry(None, "## [1.0.0] - 2024-05-01\n");
+
+        assert!(changelog.starts_with("# Changelog\n"));
+        assert!(changelog.ends_with("semver.org/spec/v2.0.0.html).\n\n## [1.0.0] - 2024-05-01\n"));
+    }
+
+    #[test]
+    fn test_prepend_entry_goes_below_unreleased() {
+        let changelog =
+            "# Changelog\n\n## [Unreleased]\n\n- Work\n\n## [1.0.0] - 2024-01-01\n\n- Old\n";
+
+        assert_eq!(
+            prepend_entry(Some(changelog), "## [1.1.0] - 2024-05-01\n\n- New\n"),
+            "# Changelog\n\n## [Unreleased]\n\n- Work\n\n\
+             ## [1.1.0] - 2024-05-01\n\n- New\n\n\
+             ## [1.0.0] - 2024-01-01\n\n- Old\n"
+        );
+    }
+
+    #[test]
+    fn test_prepend_entry_replaces_unreleased() {
+        let changelog =
+            "# Changelog\n\n## [Unreleased]\n\n- Work\n\n## [1.0.0] - 2024-01-01\n\n- Old\n";
+
+        assert_eq!(
+            prepend_entry(Some(changelog), "## [Unreleased]\n\n- More work\n"),
+            "# Changelog\n\n## [Unreleased]\n\n- More work\n\n## [1.0.0] - 2024-01-01\n\n- Old\n"
+        );
+    }
+}
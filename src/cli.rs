@@ -1,38 +1,506 @@
-use clap::{command, crate_version, Arg, ArgAction, ArgMatches};
-
-pub fn build_cli() -> ArgMatches {
-    command!()
-        .version(crate_version!())
-        .author("Sebastian Stan")
-        .about("Generates commit messages using AI")
-        .arg(
-            Arg::new("init")
-                .long("init")
-                .action(ArgAction::SetTrue)
-                .help("Initialize the config"),
-        )
-        .arg(
-            Arg::new("set-default")
-                .long("set-default")
-                .help("Sets the default config. Example: gcmgen --set-default OpenAI"),
-        )
-        .arg(
-            Arg::new("prefix")
-                .long("prefix")
-                .short('p')
-                .help("Set prefix for commit message. Example: gcmgen -p TICKET-123"),
-        )
-        .arg(
-            Arg::new("pull-request")
-                .long("pr")
-                .help("Opens up a new PR in the browser with generated description and title")
-                .action(ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("list-services")
-                .long("ls")
-                .help("Lists all configured services")
-                .action(ArgAction::SetTrue),
-        )
-        .get_matches()
+use crate::config::Task;
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    author = "Sebastian Stan",
+    about = "Generates commit messages using AI",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Uses this profile instead of the configured one. Example: gcmgen --profile work
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Options for `commit`, which runs when no subcommand is given
+    #[command(flatten)]
+    commit: CommitArgs,
+
+    #[command(flatten)]
+    legacy: LegacyArgs,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Commands {
+    /// Generates a commit message for the staged changes (default)
+    Commit(CommitArgs),
+    /// Generates a pull request for the current branch
+    Pr(PrArgs),
+    /// Reads and changes the config
+    Config(ConfigArgs),
+    /// Generates new messages for existing commits. Example: gcmgen reword HEAD~3..
+    Reword(RewordArgs),
+    /// Writes one message for a squash or merge. Example: gcmgen squash main..feature
+    Squash(SquashArgs),
+    /// Splits the staged changes into several commits, each with its own message
+    Split(SplitArgs),
+    /// Writes release notes for a range of commits. Example: gcmgen release-notes v1.0.0..v1.1.0
+    ReleaseNotes(ReleaseNotesArgs),
+    /// Suggests the next version from the changes since the latest version tag
+    Bump(BumpArgs),
+    /// Explains a change for review. Example: gcmgen explain main..
+    Explain(ExplainArgs),
+    /// Manages the prepare-commit-msg hook that fills in messages on git commit
+    Hook(HookArgs),
+    /// Lists the models available to a profile
+    Models(ModelsArgs),
+    /// Prints a shell completion script. Example: gcmgen completions zsh > _gcmgen
+    Completions { shell: Shell },
+    /// Renders the man page, or one page per subcommand with --out-dir
+    Man {
+        /// Writes gcmgen.1 and a page per subcommand to this directory
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct CommitArgs {
+    /// Set prefix for commit message. Example: gcmgen -p TICKET-123
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+    /// Generates this many alternative messages to choose from
+    #[arg(short = 'n', long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=10))]
+    pub candidates: u8,
+    /// Stages all changes to tracked files first, like git commit -a
+    #[arg(short, long)]
+    pub all: bool,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct RewordArgs {
+    /// The commit or range of commits to reword
+    #[arg(default_value = "HEAD")]
+    pub revision: String,
+    /// Set prefix for the commit messages
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct SquashArgs {
+    /// The commits to squash. Defaults to the `git merge` or `git merge --squash` in progress
+    #[arg(conflicts_with = "pr")]
+    pub range: Option<String>,
+    /// Squash-merges a pull request with gh, by default the one for the current branch
+    #[arg(long, value_name = "NUMBER")]
+    pub pr: Option<Option<String>>,
+    /// Set prefix for the commit message
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct SplitArgs {
+    /// Set prefix for the commit messages
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ReleaseNotesArgs {
+    /// The commits to describe, e.g. v1.0.0..v1.1.0 or v1.1.0.. for the unreleased ones
+    pub range: String,
+    /// The version for the heading. Defaults to the tag the range ends at, or Unreleased
+    #[arg(long)]
+    pub version: Option<String>,
+    /// Adds the notes to a changelog instead of printing them, CHANGELOG.md by default
+    #[arg(long, value_name = "FILE")]
+    pub changelog: Option<Option<PathBuf>>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct BumpArgs {
+    /// Creates an annotated tag for the new version with a generated message
+    #[arg(long)]
+    pub tag: bool,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ExplainArgs {
+    /// A commit or range of commits to explain instead of the staged changes
+    #[arg(conflicts_with = "staged")]
+    pub revision: Option<String>,
+    /// Explains the staged changes, the default
+    #[arg(long)]
+    pub staged: bool,
+}
+
+#[derive(Args, Debug, Default, PartialEq)]
+pub struct PrArgs {
+    #[command(subcommand)]
+    pub command: Option<PrCommand>,
+
+    /// Set prefix for the PR title. Example: gcmgen pr -p TICKET-123
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+
+    #[command(flatten)]
+    pub create: PrCreateArgs,
+}
+
+#[derive(Args, Debug, Default, PartialEq)]
+pub struct PrCreateArgs {
+    /// Creates the PR directly with gh and prints its URL instead of opening the browser
+    #[arg(long)]
+    pub no_web: bool,
+    /// Creates the PR as a draft
+    #[arg(long)]
+    pub draft: bool,
+    /// Requests a review from a user or team. Can be repeated
+    #[arg(long = "reviewer", value_name = "REVIEWER")]
+    pub reviewers: Vec<String>,
+    /// Adds a label to the PR. Can be repeated
+    #[arg(long = "label", value_name = "LABEL")]
+    pub labels: Vec<String>,
+    /// Assigns a user to the PR. Use "@me" to self-assign. Can be repeated
+    #[arg(long = "assignee", value_name = "ASSIGNEE")]
+    pub assignees: Vec<String>,
+    /// Adds the PR to a milestone
+    #[arg(long)]
+    pub milestone: Option<String>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum PrCommand {
+    /// Regenerates the title and description of the open PR for the current branch
+    Update(PrUpdateArgs),
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct PrUpdateArgs {
+    /// Applies the update without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+    /// Set prefix for the PR title
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum ConfigCommand {
+    /// Adds a service profile interactively
+    Init,
+    /// Configure a Forgejo/Gitea or Bitbucket Server instance for creating PRs
+    InitForge,
+    /// Lists all configured services
+    #[command(alias = "ls")]
+    List,
+    /// Sets the default profile. Example: gcmgen config set-default OpenAI
+    SetDefault { profile: String },
+    /// Uses a profile for a task instead of the default. Example: gcmgen config set-task-profile pr strong
+    SetTaskProfile { task: TaskArg, profile: String },
+    /// Prints a value. Example: gcmgen config get services.OpenAI.model
+    Get { key: String },
+    /// Sets a value. Example: gcmgen config set settings.base_branch develop
+    Set { key: String, value: String },
+    /// Removes a value. Example: gcmgen config unset task_profiles.pr
+    Unset { key: String },
+    /// Removes a service profile
+    Remove { service: String },
+    /// Opens the config file in your editor
+    Edit,
+    /// Converts config.json to config.toml, keeping all settings
+    Convert,
+    /// Checks that every service can authenticate and its model exists
+    Validate,
+}
+
+/// The task names accepted on the command line.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum TaskArg {
+    Commit,
+    Pr,
+}
+
+impl From<TaskArg> for Task {
+    fn from(task: TaskArg) -> Self {
+        match task {
+            TaskArg::Commit => Task::Commit,
+            TaskArg::Pr => Task::PullRequest,
+        }
+    }
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct HookArgs {
+    #[command(subcommand)]
+    pub command: HookCommand,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum HookCommand {
+    /// Installs the prepare-commit-msg hook in the current repository
+    Install {
+        /// Replaces an existing hook that was not installed by gcmgen
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Removes the prepare-commit-msg hook installed by gcmgen
+    Uninstall,
+    /// Runs the hook. Called by git with the message file and its source
+    #[command(hide = true)]
+    Run {
+        message_file: PathBuf,
+        source: Option<String>,
+        commit: Option<String>,
+    },
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ModelsArgs {
+    /// Lists the models for this task's profile
+    #[arg(long, value_enum, default_value = "commit")]
+    pub task: TaskArg,
+}
+
+/// The top-level flags from before the subcommands, kept so existing scripts
+/// and habits keep working.
+#[derive(Args, Debug, Default)]
+struct LegacyArgs {
+    #[arg(long, hide = true)]
+    init: bool,
+    #[arg(long, hide = true)]
+    init_forge: bool,
+    #[arg(long, hide = true, value_name = "PROFILE")]
+    set_default: Option<String>,
+    #[arg(long, hide = true, num_args = 2, value_names = ["TASK", "PROFILE"])]
+    set_task_profile: Option<Vec<String>>,
+    #[arg(long = "ls", hide = true)]
+    list_services: bool,
+    #[arg(long = "pr", hide = true)]
+    pull_request: bool,
+    #[arg(long, hide = true, requires = "pull_request")]
+    no_web: bool,
+    #[arg(long, hide = true, requires = "pull_request")]
+    draft: bool,
+    #[arg(long = "reviewer", hide = true, requires = "pull_request")]
+    reviewers: Vec<String>,
+    #[arg(long = "label", hide = true, requires = "pull_request")]
+    labels: Vec<String>,
+    #[arg(long = "assignee", hide = true, requires = "pull_request")]
+    assignees: Vec<String>,
+    #[arg(long, hide = true, requires = "pull_request")]
+    milestone: Option<String>,
+}
+
+/// Builds the command definition with the configured profile names offered
+/// wherever a profile is expected, for the generated completions.
+pub fn command_with_profiles(profiles: &[String]) -> clap::Command {
+    let with_profiles = |arg: Arg| arg.value_parser(PossibleValuesParser::new(profiles.to_vec()));
+
+    Cli::command()
+        .mut_arg("profile", with_profiles)
+        .mut_arg("set_default", with_profiles)
+        .mut_subcommand("config", |config| {
+            config
+                .mut_subcommand("set-default", |command| {
+                    command.mut_arg("profile", with_profiles)
+                })
+                .mut_subcommand("set-task-profile", |command| {
+                    command.mut_arg("profile", with_profiles)
+                })
+                .mut_subcommand("remove", |command| {
+                    command.mut_arg("service", with_profiles)
+                })
+        })
+}
+
+impl Cli {
+    /// Returns the subcommand to run, mapping the old top-level flags to the
+    /// subcommands that replaced them. Without either it runs `commit`.
+    pub fn into_command(self) -> Result<Commands, clap::Error> {
+        if let Some(command) = self.command {
+            return Ok(command);
+        }
+
+        let legacy = self.legacy;
+        let config = |command| Commands::Config(ConfigArgs { command });
+
+        let command = if legacy.init {
+            config(ConfigCommand::Init)
+        } else if legacy.init_forge {
+            config(ConfigCommand::InitForge)
+        } else if let Some(values) = legacy.set_task_profile {
+            let task = TaskArg::from_str(&values[0], true).map_err(|_| {
+                clap::Error::raw(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("Unknown task '{}', expected 'commit' or 'pr'\n", values[0]),
+                )
+            })?;
+            config(ConfigCommand::SetTaskProfile {
+                task,
+                profile: values[1].clone(),
+            })
+        } else if let Some(profile) = legacy.set_default {
+            config(ConfigCommand::SetDefault { profile })
+        } else if legacy.list_services {
+            config(ConfigCommand::List)
+        } else if legacy.pull_request {
+            Commands::Pr(PrArgs {
+                command: None,
+                prefix: self.commit.prefix,
+                create: PrCreateArgs {
+                    no_web: legacy.no_web,
+                    draft: legacy.draft,
+                    reviewers: legacy.reviewers,
+                    labels: legacy.labels,
+                    assignees: legacy.assignees,
+                    milestone: legacy.milestone,
+                },
+            })
+        } else {
+            Commands::Commit(self.commit)
+        };
+
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Commands {
+        Cli::try_parse_from(args).unwrap().into_command().unwrap()
+    }
+
+    #[test]
+    fn test_commit_is_the_default() {
+        assert_eq!(
+            parse(&["gcmgen", "-p", "T-1"]),
+            Commands::Commit(CommitArgs {
+                prefix: Some("T-1".to_string()),
+                candidates: 1,
+                all: false,
+            })
+        );
+        assert_eq!(parse(&["gcmgen"]), parse(&["gcmgen", "commit"]));
+        assert_eq!(
+            parse(&["gcmgen", "--candidates", "3"]),
+            parse(&["gcmgen", "commit", "-n", "3"])
+        );
+        assert_eq!(
+            parse(&["gcmgen", "-a"]),
+            parse(&["gcmgen", "commit", "--all"])
+        );
+        assert!(Cli::try_parse_from(["gcmgen", "--candidates", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_legacy_flags_map_to_subcommands() {
+        assert_eq!(
+            parse(&["gcmgen", "--init"]),
+            parse(&["gcmgen", "config", "init"])
+        );
+        assert_eq!(
+            parse(&["gcmgen", "--ls"]),
+            parse(&["gcmgen", "config", "list"])
+        );
+        assert_eq!(
+            parse(&["gcmgen", "--set-default", "work"]),
+            parse(&["gcmgen", "config", "set-default", "work"])
+        );
+        assert_eq!(
+            parse(&["gcmgen", "--set-task-profile", "pr", "strong"]),
+            parse(&["gcmgen", "config", "set-task-profile", "pr", "strong"])
+        );
+        assert_eq!(
+            parse(&[
+                "gcmgen",
+                "--pr",
+                "--draft",
+                "--reviewer",
+                "a",
+                "--reviewer",
+                "b",
+                "-p",
+                "T-1"
+            ]),
+            parse(&[
+                "gcmgen",
+                "pr",
+                "--draft",
+                "--reviewer",
+                "a",
+                "--reviewer",
+                "b",
+                "-p",
+                "T-1"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_explain_takes_staged_or_a_revision() {
+        assert_eq!(
+            parse(&["gcmgen", "explain", "main.."]),
+            Commands::Explain(ExplainArgs {
+                revision: Some("main..".to_string()),
+                staged: false,
+            })
+        );
+        assert!(Cli::try_parse_from(["gcmgen", "explain", "--staged", "main.."]).is_err());
+    }
+
+    #[test]
+    fn test_legacy_pr_options_require_pr() {
+        assert!(Cli::try_parse_from(["gcmgen", "--draft"]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_legacy_task_is_an_error() {
+        let cli =
+            Cli::try_parse_from(["gcmgen", "--set-task-profile", "review", "strong"]).unwrap();
+        assert!(cli.into_command().is_err());
+    }
+
+    #[test]
+    fn test_command_with_profiles_offers_profile_names() {
+        let profiles = vec!["personal".to_string(), "work".to_string()];
+        let mut command = command_with_profiles(&profiles);
+        command.build();
+
+        let names = |arg: &Arg| -> Vec<String> {
+            arg.get_possible_values()
+                .iter()
+                .map(|value| value.get_name().to_string())
+                .collect()
+        };
+        let set_default = command
+            .find_subcommand("config")
+            .and_then(|config| config.find_subcommand("set-default"))
+            .and_then(|set_default| {
+                set_default
+                    .get_arguments()
+                    .find(|arg| arg.get_id() == "profile")
+            })
+            .unwrap();
+        assert_eq!(names(set_default), profiles);
+
+        let profile = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == "profile")
+            .unwrap();
+        assert_eq!(names(profile), profiles);
+    }
+
+    #[test]
+    fn test_profile_is_global() {
+        let cli = Cli::try_parse_from(["gcmgen", "pr", "update", "--profile", "work"]).unwrap();
+        assert_eq!(cli.profile.as_deref(), Some("work"));
+    }
 }
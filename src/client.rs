@@ -1,6 +1,7 @@
 use crate::anthropic::AnthropicClient;
-use crate::config::ServiceConfig;
+use crate::config::{Provider, ServiceConfig};
 use crate::openai::OpenAIClient;
+use serde::de::DeserializeOwned;
 
 pub enum Client {
     OpenAI(OpenAIClient),
@@ -10,66 +11,595 @@ pub enum Client {
 impl Client {
     pub fn new(
         service_config: &ServiceConfig,
-        service_type: &str,
+        provider: Provider,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        match service_type {
-            "OpenAI" => Ok(Client::OpenAI(OpenAIClient::new(service_config))),
-            "Anthropic" => Ok(Client::Anthropic(AnthropicClient::new(service_config))),
-            _ => Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Unsupported service type",
-            ))),
+        match provider {
+            Provider::OpenAI => Ok(Client::OpenAI(OpenAIClient::new(service_config)?)),
+            Provider::Anthropic => Ok(Client::Anthropic(AnthropicClient::new(service_config)?)),
         }
     }
+
+    /// Checks that the API key is valid and the configured model exists.
+    pub fn check_model(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Client::OpenAI(client) => client.check_model(),
+            Client::Anthropic(client) => client.check_model(),
+        }
+    }
+
+    /// Lists the models the configured API key has access to.
+    pub fn list_models(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        match self {
+            Client::OpenAI(client) => client.list_models(),
+            Client::Anthropic(client) => client.list_models(),
+        }
+    }
+
+    /// Sets extra instructions, such as the repository's style rules, that are
+    /// sent with the requests they apply to.
+    pub fn with_instructions(self, instructions: Instructions) -> Self {
+        match self {
+            Client::OpenAI(client) => Client::OpenAI(client.with_instructions(instructions)),
+            Client::Anthropic(client) => Client::Anthropic(client.with_instructions(instructions)),
+        }
+    }
+}
+
+/// What a prompt asks the model to write, which decides the instructions sent with it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Answer {
+    /// A commit message or pull request title, which follows the message rules.
+    Message,
+    /// Anything else, such as a description or JSON, which only follows the style.
+    Other,
+}
+
+/// Extra instructions from the settings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Instructions {
+    /// Rules for commit messages and pull request titles, such as Conventional Commits.
+    pub message_rules: Option<String>,
+    /// The `prompt_style`, which applies to everything the model writes.
+    pub style: Option<String>,
+}
+
+impl Instructions {
+    /// Builds the system prompt for an answer, if there is anything to add.
+    pub fn system_prompt(&self, answer: Answer) -> Option<String> {
+        let rules = match answer {
+            Answer::Message => self.message_rules.as_deref(),
+            Answer::Other => None,
+        };
+        let instructions: Vec<&str> = rules.into_iter().chain(self.style.as_deref()).collect();
+        if instructions.is_empty() {
+            return None;
+        }
+
+        Some(format!(
+            "Follow these additional instructions:\n{}",
+            instructions.join("\n")
+        ))
+    }
+}
+
+/// Adds a system prompt after the leading system messages, so that it comes
+/// before the task instead of after it.
+pub fn insert_system_prompt(messages: &mut serde_json::Value, prompt: String) {
+    let Some(messages) = messages.as_array_mut() else {
+        return;
+    };
+
+    let position = messages
+        .iter()
+        .take_while(|message| message["role"] == "system")
+        .count();
+    messages.insert(
+        position,
+        serde_json::json!({
+            "role": "system",
+            "content": prompt
+        }),
+    );
+}
+
+/// Turns the status of a provider's model lookup into an error that says what
+/// is wrong.
+pub fn check_model_response(
+    status: reqwest::StatusCode,
+    model: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match status {
+        status if status.is_success() => Ok(()),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+            Err("Authentication failed, check the API key".into())
+        }
+        reqwest::StatusCode::NOT_FOUND => Err(format!("Model '{}' not found", model).into()),
+        status => Err(format!("Unexpected response: {}", status).into()),
+    }
+}
+
+/// Reads the model IDs from a provider's model list, sorted by name.
+pub fn parse_model_list(
+    status: reqwest::StatusCode,
+    response: &serde_json::Value,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    match status {
+        status if status.is_success() => {}
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+            return Err("Authentication failed, check the API key".into())
+        }
+        status => return Err(format!("Unexpected response: {}", status).into()),
+    }
+
+    let mut models: Vec<String> = response
+        .get("data")
+        .and_then(|data| data.as_array())
+        .ok_or_else(|| format!("Unexpected response format: {}", response))?
+        .iter()
+        .filter_map(|model| model.get("id")?.as_str().map(str::to_string))
+        .collect();
+    models.sort();
+
+    Ok(models)
+}
+
+/// Diffs larger than this are left out of PR prompts, and only the commit
+/// history is sent instead.
+const MAX_PR_DIFF_CHARS: usize = 60_000;
+
+/// Builds the user prompt for PR generation from the branch commits and diff.
+pub fn build_pr_context(diff: &str, commits: &str) -> String {
+    let mut context = String::new();
+
+    if !commits.is_empty() {
+        context.push_str(&format!(
+            "Here are the commit messages on this branch, oldest first:\n\n{}\n\n",
+            commits
+        ));
+    }
+
+    if diff.len() > MAX_PR_DIFF_CHARS && !commits.is_empty() {
+        context.push_str("The git diff is too large to include, rely on the commit messages.");
+    } else {
+        context.push_str(&format!("Here is a git diff:\n\n{}", diff));
+    }
+
+    context
+}
+
+/// What an explanation of a change should cover, sent after the commits and diff.
+pub const EXPLAIN_INSTRUCTIONS: &str = "Explain this change in plain English to someone about to review it. \
+Use these Markdown sections: \
+## Summary with what the change does and why, in a few sentences. \
+## Files with a short note for each changed file. \
+## Risks with the areas most likely to break or that deserve a careful look, such as edge cases, error handling or compatibility. \
+## Missing tests with the behavior that changed but doesn't seem to be tested. \
+Write \"None\" under a section that has nothing to say. Be specific and don't repeat the code.";
+
+/// What a squash message should look like, sent after the commits and diff.
+pub const SQUASH_INSTRUCTIONS: &str = "These commits are being squashed into one. \
+Write a single commit message that describes the combined change as a whole: \
+a concise subject line, a blank line, and a short body summarizing what changed and why. \
+Don't list the individual commits and leave out fixups such as typo fixes or review changes. \
+Only answer with the commit message. Don't include any text formatting.";
+
+/// How to group staged hunks into commits, sent after the numbered hunks.
+pub const SPLIT_INSTRUCTIONS: &str = "These staged changes may mix unrelated work. \
+Group the hunks into logical, atomic commits in the order they should be committed, \
+and write a concise and meaningful commit message for each. \
+Keep hunks that depend on each other in the same commit and use a single commit if everything belongs together. \
+Every hunk must be in exactly one commit. \
+Only answer with JSON in this form: {\"commits\": [{\"message\": \"Add login form\", \"hunks\": [1, 3]}]}";
+
+/// How to sort changes into Keep a Changelog sections, sent after the numbered changes.
+pub const CLASSIFY_INSTRUCTIONS: &str = "These changes go into the release notes. \
+Put each change in one of the sections Added, Changed, Deprecated, Removed, Fixed or Security, \
+or in Internal when it doesn't matter to users, such as tests, refactoring or CI changes. \
+Only answer with JSON that maps each change number to its section: {\"1\": \"Added\", \"2\": \"Fixed\"}";
+
+/// How to judge a version bump, sent after the commits and diff since the last release.
+pub const BUMP_INSTRUCTIONS: &str = "These are the changes since the last release. \
+Following semantic versioning, decide whether the next release is a major, minor or patch release: \
+major when the changes break compatibility for users, such as removed or renamed public APIs, options or config, \
+minor when they add functionality in a compatible way, and patch when they only fix bugs or change internals. \
+Only answer with JSON in this form: {\"bump\": \"minor\", \"justification\": \"One or two sentences on why.\"}";
+
+/// Builds the instructions for the message of an annotated release tag.
+pub fn build_tag_prompt(version: &str) -> String {
+    format!(
+        "Write the message for the annotated tag of release {}: \
+        a short title line, a blank line, and a summary of the notable changes as a plain list. \
+        Leave out internal changes. Only answer with the tag message. Don't include any text formatting.",
+        version
+    )
+}
+
+/// Builds the instructions for filling in the repository's pull request template.
+pub fn build_template_prompt(template: &str) -> String {
+    format!(
+        "Write the description by filling in this pull request template:\n\n{}\n\n\
+        Fill in every section based on the changes. Keep all headings, checklist items and their order intact.\n\
+        Only tick a checklist item when the changes clearly satisfy it. Remove HTML comments that only explain how to fill in the template.",
+        template
+    )
 }
 
 pub trait PullRequestGenerator {
     fn generate_pr_title(
         &self,
         diff: &str,
+        commits: &str,
         prefix: Option<&String>,
+        feedback: &[Feedback],
     ) -> Result<String, Box<dyn std::error::Error>>;
 
-    fn generate_pr_description(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>>;
+    fn generate_pr_description(
+        &self,
+        diff: &str,
+        commits: &str,
+        template: Option<&str>,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Explains a change for a reviewer, in the sections `EXPLAIN_INSTRUCTIONS` lists.
+    fn explain_changes(
+        &self,
+        diff: &str,
+        commits: &str,
+    ) -> Result<String, Box<dyn std::error::Error>>;
 }
 
 impl PullRequestGenerator for Client {
     fn generate_pr_title(
         &self,
         diff: &str,
+        commits: &str,
         prefix: Option<&String>,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            Client::OpenAI(client) => client.generate_pr_title(diff, commits, prefix, feedback),
+            Client::Anthropic(client) => client.generate_pr_title(diff, commits, prefix, feedback),
+        }
+    }
+
+    fn generate_pr_description(
+        &self,
+        diff: &str,
+        commits: &str,
+        template: Option<&str>,
+        feedback: &[Feedback],
     ) -> Result<String, Box<dyn std::error::Error>> {
         match self {
-            Client::OpenAI(client) => client.generate_pr_title(diff, prefix),
-            Client::Anthropic(client) => client.generate_pr_title(diff, prefix),
+            Client::OpenAI(client) => {
+                client.generate_pr_description(diff, commits, template, feedback)
+            }
+            Client::Anthropic(client) => {
+                client.generate_pr_description(diff, commits, template, feedback)
+            }
         }
     }
 
-    fn generate_pr_description(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>> {
+    fn explain_changes(
+        &self,
+        diff: &str,
+        commits: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
         match self {
-            Client::OpenAI(client) => client.generate_pr_description(diff),
-            Client::Anthropic(client) => client.generate_pr_description(diff),
+            Client::OpenAI(client) => client.explain_changes(diff, commits),
+            Client::Anthropic(client) => client.explain_changes(diff, commits),
         }
     }
 }
 
 pub trait CommitMessageGenerator {
+    /// Generates `count` alternative commit messages for the diff. With
+    /// feedback, the earlier answers are revised instead.
+    fn generate_commit_messages(
+        &self,
+        diff: &str,
+        prefix: Option<&String>,
+        count: usize,
+        feedback: &[Feedback],
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>>;
+
     fn generate_commit_message(
         &self,
         diff: &str,
         prefix: Option<&String>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(self
+            .generate_commit_messages(diff, prefix, 1, &[])?
+            .remove(0))
+    }
+
+    /// Generates one message for commits that are squashed or merged together,
+    /// from their combined diff and their own messages.
+    fn generate_squash_message(
+        &self,
+        diff: &str,
+        commits: &str,
+        prefix: Option<&String>,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Groups numbered hunks into commits, answering with the JSON plan that
+    /// `SPLIT_INSTRUCTIONS` asks for.
+    fn generate_commit_plan(
+        &self,
+        hunks: &str,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Sorts numbered changes into changelog sections, answering with the JSON
+    /// that `CLASSIFY_INSTRUCTIONS` asks for.
+    fn classify_changes(&self, changes: &str) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Suggests how to bump the version for the changes, answering with the
+    /// JSON that `BUMP_INSTRUCTIONS` asks for.
+    fn suggest_bump(&self, diff: &str, commits: &str)
+        -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Writes the message for an annotated release tag.
+    fn generate_tag_message(
+        &self,
+        diff: &str,
+        commits: &str,
+        version: &str,
+        feedback: &[Feedback],
     ) -> Result<String, Box<dyn std::error::Error>>;
 }
 
 impl CommitMessageGenerator for Client {
-    fn generate_commit_message(
+    fn generate_commit_messages(
+        &self,
+        diff: &str,
+        prefix: Option<&String>,
+        count: usize,
+        feedback: &[Feedback],
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        match self {
+            Client::OpenAI(client) => {
+                client.generate_commit_messages(diff, prefix, count, feedback)
+            }
+            Client::Anthropic(client) => {
+                client.generate_commit_messages(diff, prefix, count, feedback)
+            }
+        }
+    }
+
+    fn generate_squash_message(
         &self,
         diff: &str,
+        commits: &str,
         prefix: Option<&String>,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            Client::OpenAI(client) => {
+                client.generate_squash_message(diff, commits, prefix, feedback)
+            }
+            Client::Anthropic(client) => {
+                client.generate_squash_message(diff, commits, prefix, feedback)
+            }
+        }
+    }
+
+    fn generate_commit_plan(
+        &self,
+        hunks: &str,
+        feedback: &[Feedback],
     ) -> Result<String, Box<dyn std::error::Error>> {
         match self {
-            Client::OpenAI(client) => client.generate_commit_message(diff, prefix),
-            Client::Anthropic(client) => client.generate_commit_message(diff, prefix),
+            Client::OpenAI(client) => client.generate_commit_plan(hunks, feedback),
+            Client::Anthropic(client) => client.generate_commit_plan(hunks, feedback),
+        }
+    }
+
+    fn classify_changes(&self, changes: &str) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            Client::OpenAI(client) => client.classify_changes(changes),
+            Client::Anthropic(client) => client.classify_changes(changes),
+        }
+    }
+
+    fn suggest_bump(
+        &self,
+        diff: &str,
+        commits: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            Client::OpenAI(client) => client.suggest_bump(diff, commits),
+            Client::Anthropic(client) => client.suggest_bump(diff, commits),
+        }
+    }
+
+    fn generate_tag_message(
+        &self,
+        diff: &str,
+        commits: &str,
+        version: &str,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            Client::OpenAI(client) => client.generate_tag_message(diff, commits, version, feedback),
+            Client::Anthropic(client) => {
+                client.generate_tag_message(diff, commits, version, feedback)
+            }
+        }
+    }
+}
+
+/// Trims a generated message and puts the prefix, if any, in front of it.
+/// Revised answers may already start with it.
+pub fn apply_prefix(message: &str, prefix: Option<&String>) -> String {
+    match prefix {
+        Some(prefix) if !message.trim().starts_with(prefix.as_str()) => {
+            format!("{} {}", prefix, message.trim())
         }
+        _ => message.trim().to_string(),
+    }
+}
+
+/// An earlier answer and what the user asked to change about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feedback {
+    pub answer: String,
+    pub feedback: String,
+}
+
+/// Reads the JSON object in a model's answer, which may be wrapped in a code
+/// block or text. `what` names the answer in errors, e.g. `plan`.
+pub fn extract_json<T: DeserializeOwned>(
+    answer: &str,
+    what: &str,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let json = match (answer.find('{'), answer.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &answer[start..=end],
+        _ => return Err(format!("The {} is not JSON: {}", what, answer.trim()).into()),
+    };
+
+    serde_json::from_str(json)
+        .map_err(|e| format!("Failed to read the {}: {}\n{}", what, e, answer.trim()).into())
+}
+
+/// Continues a conversation with the earlier answers and the feedback on
+/// them, so the next answer revises the last one instead of starting over.
+pub fn append_feedback(messages: &mut serde_json::Value, feedback: &[Feedback]) {
+    let Some(messages) = messages.as_array_mut() else {
+        return;
+    };
+
+    for round in feedback {
+        messages.push(serde_json::json!({
+            "role": "assistant",
+            "content": round.answer
+        }));
+        messages.push(serde_json::json!({
+            "role": "user",
+            "content": format!(
+                "Revise your answer: {}\nOnly answer with the revised text, nothing else.",
+                round.feedback
+            )
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_pr_context_includes_commits_and_diff() {
+        let context = build_pr_context("diff --git a/x b/x", "Add x\n\nBody\n---");
+
+        assert!(context.contains("Add x"));
+        assert!(context.contains("diff --git a/x b/x"));
+    }
+
+    #[test]
+    fn test_build_pr_context_drops_large_diff_when_commits_exist() {
+        let diff = "+".repeat(MAX_PR_DIFF_CHARS + 1);
+
+        let context = build_pr_context(&diff, "Add x");
+        assert!(!context.contains(&diff));
+
+        let context = build_pr_context(&diff, "");
+        assert!(context.contains(&diff));
+    }
+
+    #[test]
+    fn test_system_prompt_only_sends_rules_with_messages() {
+        let instructions = Instructions {
+            message_rules: Some("Use Conventional Commits.".to_string()),
+            style: Some("Write in English.".to_string()),
+        };
+
+        assert_eq!(
+            instructions.system_prompt(Answer::Message).unwrap(),
+            "Follow these additional instructions:\nUse Conventional Commits.\nWrite in English."
+        );
+        assert_eq!(
+            instructions.system_prompt(Answer::Other).unwrap(),
+            "Follow these additional instructions:\nWrite in English."
+        );
+        assert_eq!(Instructions::default().system_prompt(Answer::Message), None);
+    }
+
+    #[test]
+    fn test_insert_system_prompt_goes_before_the_task() {
+        let mut messages = serde_json::json!([
+            {"role": "system", "content": "You are a helpful assistant."},
+            {"role": "user", "content": "Here is a git diff"}
+        ]);
+
+        insert_system_prompt(&mut messages, "Write in English.".to_string());
+
+        assert_eq!(messages[1]["content"], "Write in English.");
+        assert_eq!(messages[2]["role"], "user");
+    }
+
+    #[test]
+    fn test_parse_model_list() {
+        let response = serde_json::json!({
+            "data": [{"id": "gpt-4o"}, {"id": "gpt-4o-mini"}, {"id": "dall-e-3"}]
+        });
+
+        let models = parse_model_list(reqwest::StatusCode::OK, &response).unwrap();
+        assert_eq!(models, vec!["dall-e-3", "gpt-4o", "gpt-4o-mini"]);
+
+        assert!(parse_model_list(reqwest::StatusCode::UNAUTHORIZED, &response).is_err());
+        assert!(parse_model_list(reqwest::StatusCode::OK, &serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn test_apply_prefix() {
+        let prefix = "TICKET-1".to_string();
+
+        assert_eq!(
+            apply_prefix(" Fix typo\n", Some(&prefix)),
+            "TICKET-1 Fix typo"
+        );
+        assert_eq!(apply_prefix(" Fix typo\n", None), "Fix typo");
+        assert_eq!(
+            apply_prefix("TICKET-1 Fix typo", Some(&prefix)),
+            "TICKET-1 Fix typo"
+        );
+    }
+
+    #[test]
+    fn test_append_feedback_continues_the_conversation() {
+        let mut messages = serde_json::json!([{"role": "user", "content": "diff"}]);
+        append_feedback(
+            &mut messages,
+            &[Feedback {
+                answer: "Refactor parser".to_string(),
+                feedback: "don't say refactor".to_string(),
+            }],
+        );
+
+        let messages = messages.as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["role"], "assistant");
+        assert_eq!(messages[1]["content"], "Refactor parser");
+        assert_eq!(messages[2]["role"], "user");
+        assert!(messages[2]["content"]
+            .as_str()
+            .unwrap()
+            .contains("don't say refactor"));
+    }
+
+    #[test]
+    fn test_extract_json_reads_wrapped_answers() {
+        let value: serde_json::Value =
+            extract_json("Sure:\n```json\n{\"commits\": []}\n```", "plan").unwrap();
+        assert_eq!(value["commits"], serde_json::json!([]));
+
+        let error = extract_json::<serde_json::Value>("one commit", "plan").unwrap_err();
+        assert_eq!(error.to_string(), "The plan is not JSON: one commit");
+        assert!(extract_json::<serde_json::Value>("{not json}", "plan")
+            .unwrap_err()
+            .to_string()
+            .starts_with("Failed to read the plan"));
     }
 }
@@ -0,0 +1,159 @@
+use crate::changelog::{self, Section};
+use crate::cli::BumpArgs;
+use crate::client::{extract_json, Client, CommitMessageGenerator};
+use crate::commands::{load_config, load_settings};
+use crate::config::Task;
+use crate::git::{self, GitError};
+use crate::review::{self, Action, Next, Review};
+use crate::semver::{self, Level};
+use crate::vim::Vim;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct Suggestion {
+    bump: String,
+    justification: String,
+}
+
+pub fn run(args: &BumpArgs, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let tags = git::get_merged_tags()?;
+    let Some((tag, version)) = semver::latest_tag(&tags) else {
+        return Err("No version tag found. Tag the first release, e.g. git tag -a v0.1.0".into());
+    };
+
+    let log = git::get_log(tag, "HEAD")?;
+    if log.is_empty() {
+        println!("No commits since {}.", tag);
+        return Ok(());
+    }
+
+    let config = load_config();
+    let settings = load_settings(&config)?;
+    let (_, service_config, provider) = config.resolve_profile(profile, Task::Commit)?;
+    let client =
+        Client::new(&service_config, provider)?.with_instructions(settings.prompt_instructions());
+
+    let commits = git::get_commits(tag, "HEAD")?;
+    let diff = match git::get_range_diff(tag, "HEAD", &settings.ignore) {
+        Ok(diff) => diff,
+        Err(GitError::EmptyDiff) => String::new(),
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let breaking = semver::find_breaking_commits(&commits);
+    let removed = semver::find_removed_items(&diff);
+    let detected = if !breaking.is_empty() || !removed.is_empty() {
+        Level::Major
+    } else if log.iter().any(|entry| {
+        matches!(
+            changelog::parse_conventional(&entry.subject),
+            Some((Some(Section::Added), _))
+        )
+    }) {
+        Level::Minor
+    } else {
+        Level::Patch
+    };
+
+    println!("Analyzing {} commit(s) since {}...", log.len(), tag);
+    let (suggested, justification) = parse_suggestion(&client.suggest_bump(&diff, &commits)?)?;
+    let level = suggested.max(detected);
+    let next = version.bump(level);
+    let prefix = if tag.starts_with('v') { "v" } else { "" };
+    let name = format!("{}{}", prefix, next);
+
+    println!("\nSuggested bump: {}, {} -> {}\n", level, tag, name);
+    println!("{}", justification);
+    if level > suggested {
+        println!("\nRaised from {} because of the changes below.", suggested);
+    }
+    if !breaking.is_empty() {
+        println!("\nBreaking changes:");
+        for subject in &breaking {
+            println!("  - {}", subject);
+        }
+    }
+    if !removed.is_empty() {
+        println!("\nRemoved public items:");
+        for item in &removed {
+            println!("  - {}", item);
+        }
+    }
+
+    if !args.tag {
+        return Ok(());
+    }
+    if git::is_tag(&name) {
+        return Err(format!("Tag {} already exists", name).into());
+    }
+
+    println!("\nGenerating tag message...");
+    let mut review = Review::new();
+    review.add_candidates(vec![client.generate_tag_message(
+        &diff,
+        &commits,
+        &name,
+        &[],
+    )?]);
+
+    loop {
+        println!("\nGenerated tag message:\n\n{}\n", review.current());
+
+        let action = review::prompt_action(Action::available(review.candidates().len() > 1))?;
+        let event = review.event_for(
+            action,
+            |message| Vim::new().edit_message(message),
+            |message| review::first_line(message),
+        )?;
+
+        match review.handle(event) {
+            Next::Prompt => {}
+            Next::Generate => {
+                println!("Generating tag message...");
+                let feedback = review.feedback(|message| message);
+                review.add_candidates(vec![
+                    client.generate_tag_message(&diff, &commits, &name, &feedback)?
+                ]);
+            }
+            Next::Finish(message) => {
+                git::create_tag(&name, &message)?;
+                println!(
+                    "Created tag {}. Push it with: git push origin {}",
+                    name, name
+                );
+                return Ok(());
+            }
+            Next::Cancel => {
+                println!("Tag skipped. No tag was created.");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Reads the model's `{"bump": "minor", "justification": "..."}` answer.
+fn parse_suggestion(answer: &str) -> Result<(Level, String), Box<dyn std::error::Error>> {
+    let suggestion: Suggestion = extract_json(answer, "suggestion")?;
+    let level = Level::from_name(&suggestion.bump)
+        .ok_or_else(|| format!("Unknown version bump '{}'", suggestion.bump))?;
+
+    Ok((level, suggestion.justification.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_suggestion() {
+        let answer =
+            "```json\n{\"bump\": \"Minor\", \"justification\": \" Adds the bump command. \"}\n```";
+
+        assert_eq!(
+            parse_suggestion(answer).unwrap(),
+            (Level::Minor, "Adds the bump command.".to_string())
+        );
+        assert!(parse_suggestion(r#"{"bump": "huge", "justification": ""}"#).is_err());
+        assert!(parse_suggestion("minor").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::cli::CommitArgs;
+use crate::client::{Client, CommitMessageGenerator};
+use crate::commands::{load_config, load_settings, resolve_prefix};
+use crate::config::Task;
+use crate::git::{self, GitError};
+use crate::review::{self, Action, Next, Review};
+use crate::vim::Vim;
+use inquire::MultiSelect;
+use std::io::{self, IsTerminal};
+
+pub fn run(args: &CommitArgs, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config();
+    let settings = load_settings(&config)?;
+    let (_, service_config, provider) = config.resolve_profile(profile, Task::Commit)?;
+    let client =
+        Client::new(&service_config, provider)?.with_instructions(settings.prompt_instructions());
+    let prefix = resolve_prefix(args.prefix.as_ref(), &settings)?;
+    let count = args.candidates.into();
+
+    if !args.all {
+        return generate_and_commit(&client, &settings.ignore, prefix.as_ref(), count, true)
+            .map(|_| ());
+    }
+
+    // Remember the index, so the changes --all stages don't stay staged
+    // unless they are committed
+    let index = git::write_index_tree()?;
+    git::stage_tracked()?;
+    let result = generate_and_commit(&client, &settings.ignore, prefix.as_ref(), count, false);
+    if !matches!(result, Ok(true)) {
+        git::restore_index(&index)?;
+        println!("Unstaged the changes staged by --all.");
+    }
+
+    result.map(|_| ())
+}
+
+/// Generates a message for the staged changes and commits them once accepted.
+/// Returns whether a commit was made.
+fn generate_and_commit(
+    client: &Client,
+    ignore: &[String],
+    prefix: Option<&String>,
+    count: usize,
+    offer: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    // Get the diff from Git, offering to stage files when nothing is staged
+    let diff = match git::get_diff(ignore) {
+        Err(GitError::EmptyDiff) if offer && offer_staging()? => git::get_diff(ignore),
+        diff => diff,
+    };
+    let diff = match diff {
+        Ok(diff) => diff,
+        Err(GitError::EmptyDiff) => {
+            eprintln!("Error: {}", GitError::EmptyDiff);
+            return Ok(false); // Not an actual error, just exit gracefully
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Err(Box::new(e));
+        }
+    };
+    println!("{}", git::get_diff_stat(&["--staged"], ignore)?);
+
+    let mut review = Review::new();
+    review.add_candidates(client.generate_commit_messages(&diff, prefix, count, &[])?);
+
+    loop {
+        // Display the generated commit message to the user
+        let candidates = review.candidates().len();
+        if candidates > 1 {
+            println!(
+                "\nGenerated commit message ({} of {}):\n\n{}\n",
+                review.position(),
+                candidates,
+                review.current()
+            );
+        } else {
+            println!("\nGenerated commit message:\n\n{}\n", review.current());
+        }
+
+        let action = review::prompt_action(Action::available(candidates > 1))?;
+        let event = review.event_for(
+            action,
+            |message| Vim::new().edit_message(message),
+            |message| review::first_line(message),
+        )?;
+
+        match review.handle(event) {
+            Next::Prompt => {}
+            Next::Generate => {
+                println!("Generating commit message...");
+                let feedback = review.feedback(|message| message);
+                review.add_candidates(
+                    client.generate_commit_messages(&diff, prefix, count, &feedback)?,
+                );
+            }
+            Next::Finish(commit_message) => {
+                git::commit(&commit_message)?;
+                println!("Committed with message: {}", commit_message);
+                return Ok(true);
+            }
+            Next::Cancel => {
+                println!("Commit skipped. Nothing was committed.");
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// Asks which changed files to stage. Returns whether anything was staged.
+fn offer_staging() -> Result<bool, Box<dyn std::error::Error>> {
+    let files = git::get_unstaged_files()?;
+    if files.is_empty() || !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    let files = MultiSelect::new(
+        "Nothing is staged. Which files do you want to stage?",
+        files,
+    )
+    .with_help_message("↑↓ to move, space to select, → to select all, enter to confirm")
+    .prompt()?;
+    if files.is_empty() {
+        return Ok(false);
+    }
+
+    git::stage_files(&files)?;
+    Ok(true)
+}
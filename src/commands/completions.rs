@@ -0,0 +1,18 @@
+use crate::cli;
+use crate::config::Config;
+use clap_complete::Shell;
+use std::io;
+
+/// Prints the completion script, with the currently configured profile names
+/// baked in. Rerun it after adding or removing profiles.
+pub fn run(shell: Shell) -> Result<(), Box<dyn std::error::Error>> {
+    let mut profiles: Vec<String> = Config::load_with_env()
+        .map(|config| config.services.into_keys().collect())
+        .unwrap_or_default();
+    profiles.sort();
+
+    let mut command = cli::command_with_profiles(&profiles);
+    clap_complete::generate(shell, &mut command, "gcmgen", &mut io::stdout());
+
+    Ok(())
+}
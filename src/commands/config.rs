@@ -0,0 +1,301 @@
+use crate::cli::ConfigCommand;
+use crate::client::Client;
+use crate::commands::{load_config, load_config_file};
+use crate::config::{
+    self, ApiToken, Config, ForgeConfig, ForgeKind, Provider, ServiceConfig, Task,
+};
+use crate::keyring;
+use clap::ValueEnum;
+use inquire::{Confirm, Password, Select, Text};
+use std::process::exit;
+
+pub fn run(command: &ConfigCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        ConfigCommand::Init => init()?,
+        ConfigCommand::InitForge => init_forge()?,
+        ConfigCommand::List => println!("{}", load_config()),
+        ConfigCommand::SetDefault { profile } => {
+            let mut config = load_config_file();
+            match config.set_default_service(profile) {
+                Ok(_) => println!("Default service set to '{}'.", profile),
+                Err(e) => {
+                    eprintln!("Error setting default service: {}", e);
+                    exit(1);
+                }
+            }
+        }
+        ConfigCommand::SetTaskProfile { task, profile } => {
+            let mut config = load_config_file();
+            match config.set_task_profile(Task::from(*task), profile) {
+                Ok(_) => println!(
+                    "Profile for '{}' set to '{}'.",
+                    task.to_possible_value().unwrap().get_name(),
+                    profile
+                ),
+                Err(e) => {
+                    eprintln!("Error setting task profile: {}", e);
+                    exit(1);
+                }
+            }
+        }
+        ConfigCommand::Get { key } => {
+            let config = load_config_file();
+            // Never print plaintext tokens in full, also when they are part of a table
+            let mut value = config.get_value(key)?;
+            config::mask_tokens(&mut value);
+            match value {
+                serde_json::Value::String(token) if key.ends_with("api_token") => {
+                    println!("{}", config::mask_token(&token))
+                }
+                serde_json::Value::String(value) => println!("{}", value),
+                value => println!("{}", serde_json::to_string_pretty(&value)?),
+            }
+        }
+        ConfigCommand::Set { key, value } => {
+            let mut config = load_config_file();
+            config.set_value(key, value)?;
+            config.save()?;
+            println!("Set '{}'.", key);
+        }
+        ConfigCommand::Unset { key } => {
+            let mut config = load_config_file();
+            config.unset_value(key)?;
+            config.save()?;
+            println!("Unset '{}'.", key);
+        }
+        ConfigCommand::Remove { service } => {
+            let mut config = load_config_file();
+            config.remove_service(service)?;
+            config.save()?;
+            println!("Removed service '{}'.", service);
+        }
+        ConfigCommand::Edit => {
+            let editor = ["VISUAL", "EDITOR"]
+                .iter()
+                .find_map(|name| std::env::var(name).ok().filter(|editor| !editor.is_empty()))
+                .unwrap_or_else(|| "vi".to_string());
+            // Run it through the shell like git does, so editors with arguments
+            // such as `code --wait` work
+            let status = std::process::Command::new("sh")
+                .args(["-c", &format!("{} \"$@\"", editor), &editor])
+                .arg(Config::get_config_file())
+                .status()?;
+            if !status.success() {
+                return Err(format!("The editor '{}' failed with {}", editor, status).into());
+            }
+
+            if let Err(e) = Config::load() {
+                eprintln!("Error: The config file is invalid: {}", e);
+                exit(1);
+            }
+        }
+        ConfigCommand::Convert => {
+            let backup = Config::convert_to_toml()?;
+            println!(
+                "Converted the config to {}. The old file was saved to {}.",
+                Config::get_config_file().display(),
+                backup.display()
+            );
+        }
+        ConfigCommand::Validate => {
+            if !validate_config(&load_config_file()) {
+                exit(1);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn init() -> Result<(), Box<dyn std::error::Error>> {
+    let provider = Select::new("Choose your AI service:", Provider::all()).prompt()?;
+
+    let default_models =
+        config::default_model(&provider.to_string()).expect("every provider has a default model");
+
+    // Prompt user for the model name
+    let model = Text::new("Enter the model name (or the default values will be used):")
+        .with_initial_value(default_models)
+        .prompt()?;
+
+    // Several profiles can use the same provider, e.g. with a work and a personal key
+    let profile = Text::new("Enter a name for this profile:")
+        .with_initial_value(&provider.to_string())
+        .prompt()?;
+
+    // Prompt user for the API key and where to store it
+    let api_token = prompt_api_token("Enter your API key:", &profile)?;
+
+    // Construct the service configuration
+    let service_config = ServiceConfig {
+        provider: Some(provider),
+        api_token,
+        model,
+    };
+
+    // Load existing configuration or create a new one
+    let mut config = match Config::load() {
+        Ok(config) => config,
+        Err(_) => Config {
+            default_service: profile.clone(),
+            ..Default::default()
+        },
+    };
+
+    // Update the configuration with the chosen service
+    config.services.insert(profile.clone(), service_config);
+    if config.default_service != profile
+        && Confirm::new("Make this the default profile?")
+            .with_default(true)
+            .prompt()?
+    {
+        config.default_service = profile;
+    }
+
+    // Save the configuration
+    config.save()?;
+    println!("Configuration saved successfully.");
+    Ok(())
+}
+
+fn init_forge() -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = load_config_file();
+    let kinds = vec![ForgeKind::Forgejo, ForgeKind::Bitbucket];
+    let kind = Select::new("Choose the forge type:", kinds).prompt()?;
+    let name = Text::new("Enter a name for this forge:").prompt()?;
+    let base_url = Text::new("Enter the base URL of the forge:")
+        .with_placeholder("https://codeberg.org")
+        .prompt()?;
+    let api_token = prompt_api_token("Enter your API token:", &name)?;
+
+    config.forges.insert(
+        name,
+        ForgeConfig {
+            kind,
+            base_url,
+            api_token,
+        },
+    );
+    config.save()?;
+    println!("Configuration saved successfully.");
+    Ok(())
+}
+
+/// Checks every profile against its provider and the references between
+/// config entries. Prints a line per check and returns whether all passed.
+fn validate_config(config: &Config) -> bool {
+    let mut valid = true;
+    let mut report = |name: &str, result: Result<(), Box<dyn std::error::Error>>| match result {
+        Ok(()) => println!("OK      {}", name),
+        Err(e) => {
+            println!("FAILED  {}: {}", name, e);
+            valid = false;
+        }
+    };
+
+    let mut names: Vec<&String> = config.services.keys().collect();
+    names.sort();
+    for name in names {
+        let service_config = &config.services[name];
+        let result = service_config
+            .provider(name)
+            .ok_or_else(|| "No provider configured".into())
+            .and_then(|provider| Client::new(service_config, provider))
+            .and_then(|client| client.check_model());
+        report(&format!("service {}", name), result);
+    }
+
+    for (task, profile) in [
+        ("default service", Some(&config.default_service)),
+        ("commit profile", config.task_profiles.commit.as_ref()),
+        ("pr profile", config.task_profiles.pr.as_ref()),
+    ] {
+        if let Some(profile) = profile {
+            let result = if config.services.contains_key(profile) {
+                Ok(())
+            } else {
+                Err(format!("Service '{}' not found", profile).into())
+            };
+            report(task, result);
+        }
+    }
+
+    let mut names: Vec<&String> = config.forges.keys().collect();
+    names.sort();
+    for name in names {
+        let result = config.forges[name]
+            .api_token
+            .resolve()
+            .map(|_| ())
+            .map_err(|e| e.into());
+        report(&format!("forge {}", name), result);
+    }
+
+    valid
+}
+
+pub fn offer_toml_conversion() -> Result<(), Box<dyn std::error::Error>> {
+    let convert = Confirm::new(
+        "gcmgen now keeps its config in config.toml, which can hold comments. Convert your config.json?",
+    )
+    .with_default(true)
+    .prompt()?;
+
+    if convert {
+        let backup = Config::convert_to_toml()?;
+        println!(
+            "Converted the config to {}. The old file was saved to {}.",
+            Config::get_config_file().display(),
+            backup.display()
+        );
+    } else {
+        Config::decline_toml_conversion()?;
+        println!("Keeping config.json. You can convert it later with 'gcmgen config convert'.");
+    }
+
+    Ok(())
+}
+
+/// Asks where an API token should live and returns the reference to store in
+/// the config. Tokens for the keyring are stored right away under `account`.
+fn prompt_api_token(message: &str, account: &str) -> Result<ApiToken, Box<dyn std::error::Error>> {
+    let storages = vec![
+        "OS keyring",
+        "Command that prints the token",
+        "Environment variable",
+        "Config file (plaintext)",
+    ];
+    let storage = Select::new("Where should the token be stored?", storages).prompt()?;
+
+    let api_token = match storage {
+        "Command that prints the token" => ApiToken::Command {
+            command: Text::new("Enter the command:")
+                .with_placeholder("pass show openai")
+                .prompt()?,
+        },
+        "Environment variable" => ApiToken::Env {
+            env: Text::new("Enter the name of the environment variable:").prompt()?,
+        },
+        storage => {
+            let token = Password::new(message)
+                .with_display_mode(Password::DEFAULT_DISPLAY_MODE)
+                .prompt()?;
+
+            if storage == "OS keyring" {
+                keyring::set_password(account, &token)?;
+                ApiToken::Keyring {
+                    keyring: account.to_string(),
+                }
+            } else {
+                ApiToken::Plain(token)
+            }
+        }
+    };
+
+    // Warn early instead of failing on the first request
+    if let Err(e) = api_token.resolve() {
+        eprintln!("Warning: The token can't be read yet: {}", e);
+    }
+
+    Ok(api_token)
+}
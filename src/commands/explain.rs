@@ -0,0 +1,49 @@
+use crate::cli::ExplainArgs;
+use crate::client::{Client, PullRequestGenerator};
+use crate::commands::{load_config, load_settings, split_range};
+use crate::config::Task;
+use crate::git::{self, GitError};
+
+pub fn run(args: &ExplainArgs, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config();
+    let settings = load_settings(&config)?;
+    let (_, service_config, provider) = config.resolve_profile(profile, Task::PullRequest)?;
+    let client =
+        Client::new(&service_config, provider)?.with_instructions(settings.prompt_instructions());
+    let ignore = &settings.ignore;
+
+    // The staged changes, a range with its commit messages, or a single commit
+    let (diff, commits, stat) = match &args.revision {
+        None => (
+            git::get_diff(ignore),
+            String::new(),
+            git::get_diff_stat(&["--staged"], ignore)?,
+        ),
+        Some(revision) => match split_range(revision) {
+            Some((from, to)) => (
+                git::get_range_diff(&from, &to, ignore),
+                git::get_commits(&from, &to)?,
+                git::get_diff_stat(&[&format!("{}...{}", from, to)], ignore)?,
+            ),
+            None => (
+                git::get_commit_diff(revision, ignore),
+                git::get_commit_message(revision)?,
+                git::get_commit_stat(revision)?,
+            ),
+        },
+    };
+    let diff = match diff {
+        Ok(diff) => diff,
+        Err(GitError::EmptyDiff) => {
+            eprintln!("Error: There are no changes to explain");
+            return Ok(());
+        }
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    println!("{}", stat);
+    println!("Explaining the changes...\n");
+    println!("{}", client.explain_changes(&diff, &commits)?);
+
+    Ok(())
+}
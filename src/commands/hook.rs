@@ -0,0 +1,151 @@
+use crate::cli::HookCommand;
+use crate::client::{Client, CommitMessageGenerator};
+use crate::commands::{load_settings, resolve_prefix};
+use crate::config::{Config, Task};
+use crate::git::{self, GitError};
+use std::fs;
+use std::path::Path;
+
+const HOOK_NAME: &str = "prepare-commit-msg";
+
+/// Identifies hooks written by gcmgen, so foreign hooks are never replaced.
+const HOOK_MARKER: &str = "# Installed by gcmgen";
+
+pub fn run(command: &HookCommand, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        HookCommand::Install { force } => install(*force, profile),
+        HookCommand::Uninstall => uninstall(),
+        HookCommand::Run {
+            message_file,
+            source,
+            ..
+        } => {
+            if !should_generate(source.as_deref()) {
+                return Ok(());
+            }
+            // A failing hook aborts the commit, so only warn and leave the message empty
+            if let Err(e) = fill_message(message_file, profile) {
+                eprintln!("gcmgen: Could not generate a commit message: {}", e);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn install(force: bool, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let hooks_dir = git::get_git_path("hooks")?;
+    let path = hooks_dir.join(HOOK_NAME);
+
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if !existing.contains(HOOK_MARKER) && !force {
+            return Err(format!(
+                "{} already exists. Use --force to replace it",
+                path.display()
+            )
+            .into());
+        }
+    }
+
+    fs::create_dir_all(&hooks_dir)?;
+    fs::write(&path, hook_script(profile))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    println!("Installed the {} hook at {}.", HOOK_NAME, path.display());
+    Ok(())
+}
+
+fn uninstall() -> Result<(), Box<dyn std::error::Error>> {
+    let path = git::get_git_path("hooks")?.join(HOOK_NAME);
+
+    match fs::read_to_string(&path) {
+        Ok(existing) if existing.contains(HOOK_MARKER) => {
+            fs::remove_file(&path)?;
+            println!("Removed the {} hook.", HOOK_NAME);
+        }
+        Ok(_) => return Err(format!("{} was not installed by gcmgen", path.display()).into()),
+        Err(_) => println!("No {} hook is installed.", HOOK_NAME),
+    }
+
+    Ok(())
+}
+
+fn hook_script(profile: Option<&str>) -> String {
+    let profile = profile
+        .map(|profile| format!(" --profile '{}'", profile.replace('\'', r"'\''")))
+        .unwrap_or_default();
+
+    format!(
+        "#!/bin/sh\n{}\nexec gcmgen hook run{} \"$@\"\n",
+        HOOK_MARKER, profile
+    )
+}
+
+/// Git passes a source when the message already comes from somewhere, such as
+/// `-m`, a merge or an amended commit. Only plain commits get a generated message.
+fn should_generate(source: Option<&str>) -> bool {
+    matches!(source, None | Some("template"))
+}
+
+fn fill_message(
+    message_file: &Path,
+    profile: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load_with_env()?;
+    let settings = load_settings(&config)?;
+    let diff = match git::get_diff(&settings.ignore) {
+        Ok(diff) => diff,
+        Err(GitError::EmptyDiff) => return Ok(()),
+        Err(e) => return Err(Box::new(e)),
+    };
+    let (_, service_config, provider) = config.resolve_profile(profile, Task::Commit)?;
+    let client =
+        Client::new(&service_config, provider)?.with_instructions(settings.prompt_instructions());
+    let prefix = resolve_prefix(None, &settings)?;
+
+    let message = client.generate_commit_message(&diff, prefix.as_ref())?;
+    let existing = fs::read_to_string(message_file)?;
+    fs::write(message_file, prepend_message(&message, &existing))?;
+
+    Ok(())
+}
+
+/// Puts the message above what git already wrote, usually the commented status.
+fn prepend_message(message: &str, existing: &str) -> String {
+    format!("{}\n{}", message.trim_end(), existing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_generate() {
+        assert!(should_generate(None));
+        assert!(should_generate(Some("template")));
+        assert!(!should_generate(Some("message")));
+        assert!(!should_generate(Some("merge")));
+        assert!(!should_generate(Some("squash")));
+        assert!(!should_generate(Some("commit")));
+    }
+
+    #[test]
+    fn test_hook_script() {
+        assert_eq!(
+            hook_script(None),
+            "#!/bin/sh\n# Installed by gcmgen\nexec gcmgen hook run \"$@\"\n"
+        );
+        assert!(hook_script(Some("work")).contains("exec gcmgen hook run --profile 'work' \"$@\""));
+    }
+
+    #[test]
+    fn test_prepend_message() {
+        assert_eq!(
+            prepend_message("Fix typo\n", "\n# Please enter the commit message\n"),
+            "Fix typo\n\n# Please enter the commit message\n"
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use crate::cli::Cli;
+use clap::CommandFactory;
+use std::io;
+use std::path::Path;
+
+/// Prints the man page, or writes a page per subcommand to `out_dir`.
+pub fn run(out_dir: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let command = Cli::command();
+
+    match out_dir {
+        Some(out_dir) => {
+            std::fs::create_dir_all(out_dir)?;
+            clap_mangen::generate_to(command, out_dir)?;
+            println!("Man pages written to {}.", out_dir.display());
+        }
+        None => clap_mangen::Man::new(command).render(&mut io::stdout())?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+pub mod bump;
+pub mod commit;
+pub mod completions;
+pub mod config;
+pub mod explain;
+pub mod hook;
+pub mod man;
+pub mod models;
+pub mod pr;
+pub mod release_notes;
+pub mod reword;
+pub mod split;
+pub mod squash;
+
+use crate::config::Config;
+use crate::git;
+use crate::settings::Settings;
+use std::process::exit;
+
+/// Loads the config with environment overrides, exiting with a hint if there
+/// is neither a config file nor an API key in the environment.
+pub fn load_config() -> Config {
+    // Environment variables take precedence over the config file, which is optional
+    match Config::load_with_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}. Please run 'gcmgen config init' to initialize, or set OPENAI_API_KEY or ANTHROPIC_API_KEY.", e);
+            exit(1);
+        }
+    }
+}
+
+/// Loads the config file for commands that change it, exiting if there is none.
+pub fn load_config_file() -> Config {
+    match Config::load() {
+        Ok(config) => config,
+        Err(_) => {
+            eprintln!(
+                "Error: No config file found. Please run 'gcmgen config init' to initialize."
+            );
+            exit(1);
+        }
+    }
+}
+
+/// Layers the repository's `.gcmgen.toml` on top of the user settings. Outside
+/// of a repository only the user settings apply.
+pub fn load_settings(config: &Config) -> Result<Settings, Box<dyn std::error::Error>> {
+    let settings = config.settings.clone();
+
+    let Ok(root) = git::get_repo_root() else {
+        return Ok(settings);
+    };
+
+    Ok(match Settings::load_repo(&root)? {
+        Some(repo_settings) => settings.merge(repo_settings),
+        None => settings,
+    })
+}
+
+/// An explicit prefix wins over the ticket found in the branch name.
+pub fn resolve_prefix(
+    prefix: Option<&String>,
+    settings: &Settings,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    Ok(match prefix {
+        Some(prefix) => Some(prefix.clone()),
+        None if settings.ticket_regex.is_some() => {
+            settings.find_ticket(&git::get_current_branch()?)?
+        }
+        None => None,
+    })
+}
+
+/// Splits `from..to` or `from...to`, where `to` defaults to HEAD.
+pub fn split_range(range: &str) -> Option<(String, String)> {
+    let (from, to) = range.split_once("...").or_else(|| range.split_once(".."))?;
+    if from.is_empty() {
+        return None;
+    }
+    let to = if to.is_empty() { "HEAD" } else { to };
+
+    Some((from.to_string(), to.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_range() {
+        assert_eq!(
+            split_range("main..feature"),
+            Some(("main".to_string(), "feature".to_string()))
+        );
+        assert_eq!(
+            split_range("main...feature"),
+            Some(("main".to_string(), "feature".to_string()))
+        );
+        assert_eq!(
+            split_range("main.."),
+            Some(("main".to_string(), "HEAD".to_string()))
+        );
+        assert_eq!(split_range("..feature"), None);
+        assert_eq!(split_range("main"), None);
+    }
+}
@@ -0,0 +1,24 @@
+use crate::cli::ModelsArgs;
+use crate::client::Client;
+use crate::commands::load_config;
+use crate::config::Task;
+
+/// Prints the models available to the profile, marking the configured one.
+pub fn run(args: &ModelsArgs, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config();
+    let (name, service_config, provider) =
+        config.resolve_profile(profile, Task::from(args.task))?;
+    let client = Client::new(&service_config, provider)?;
+
+    println!("Models available to profile '{}' ({}):", name, provider);
+    for model in client.list_models()? {
+        let marker = if model == service_config.model {
+            "*"
+        } else {
+            " "
+        };
+        println!("{} {}", marker, model);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use crate::cli::{PrArgs, PrCommand};
+use crate::client::{Client, PullRequestGenerator};
+use crate::commands::{load_config, load_settings, resolve_prefix};
+use crate::config::{Config, Task};
+use crate::forge::{Forge, PullRequestCreator, PullRequestOptions};
+use crate::git::{self, GitError};
+use crate::review::{self, Action, Next, Review};
+use crate::settings::Settings;
+use crate::vim::Vim;
+use crate::{gh, preserve, template};
+use inquire::{Confirm, Select};
+use std::fmt;
+
+pub fn run(args: &PrArgs, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config();
+    let settings = load_settings(&config)?;
+    let (_, service_config, provider) = config.resolve_profile(profile, Task::PullRequest)?;
+    let client =
+        Client::new(&service_config, provider)?.with_instructions(settings.prompt_instructions());
+
+    match &args.command {
+        Some(PrCommand::Update(update_args)) => {
+            let prefix = resolve_prefix(update_args.prefix.as_ref(), &settings)?;
+            update_pull_request(
+                &client,
+                &config,
+                &settings,
+                prefix.as_ref(),
+                update_args.yes,
+            )
+        }
+        None => create_pull_request(&client, &config, &settings, args),
+    }
+}
+
+fn create_pull_request(
+    client: &Client,
+    config: &Config,
+    settings: &Settings,
+    args: &PrArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let prefix = resolve_prefix(args.prefix.as_ref(), settings)?;
+    let prefix = prefix.as_ref();
+    let forge = Forge::detect(config)?;
+    let template = select_pr_template(settings)?;
+    let base_branch = Some(settings.base_branch());
+    let pr_options = PullRequestOptions {
+        web: !args.create.no_web,
+        draft: args.create.draft,
+        reviewers: args.create.reviewers.clone(),
+        labels: args.create.labels.clone(),
+        assignees: args.create.assignees.clone(),
+        milestone: args.create.milestone.clone(),
+    };
+
+    // Get the diff from Git
+    let branch_diff = match git::get_branch_diff(base_branch, &settings.ignore) {
+        Ok(branch_diff) => branch_diff,
+        Err(GitError::EmptyDiff) => {
+            eprintln!("Error: {}", GitError::EmptyDiff);
+            return Ok(()); // Not an actual error, just exit gracefully
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Err(Box::new(e));
+        }
+    };
+    let commits = git::get_branch_commits(base_branch)?;
+    println!(
+        "{}",
+        git::get_diff_stat(&[settings.base_branch()], &settings.ignore)?
+    );
+
+    let generate = |review: &Review<Draft>| -> Result<Draft, Box<dyn std::error::Error>> {
+        // The feedback applies to both, the model keeps what already fits
+        let title_feedback = review.feedback(|draft| &draft.title);
+        let description_feedback = review.feedback(|draft| &draft.description);
+        Ok(Draft {
+            title: client.generate_pr_title(&branch_diff, &commits, prefix, &title_feedback)?,
+            description: client.generate_pr_description(
+                &branch_diff,
+                &commits,
+                template.as_deref(),
+                &description_feedback,
+            )?,
+        })
+    };
+
+    let mut review = Review::new();
+    review.add_candidates(vec![generate(&review)?]);
+
+    loop {
+        let draft = review.current();
+        println!("\nGenerated PR Title:\n{}\n", draft.title);
+        println!("Generated PR Description:\n{}\n", draft.description);
+
+        let candidates = review.candidates().len();
+        let action = review::prompt_action(Action::available(candidates > 1))?;
+        let event = review.event_for(
+            action,
+            |draft| Ok(Draft::parse(&Vim::new().edit_message(&draft.to_string())?)),
+            |draft| draft.title.clone(),
+        )?;
+
+        match review.handle(event) {
+            Next::Prompt => {}
+            Next::Generate => {
+                println!("Generating PR...");
+                let draft = generate(&review)?;
+                review.add_candidates(vec![draft]);
+            }
+            Next::Finish(draft) => {
+                if (!pr_options.opens_browser() || !forge.uses_cli()) && !git::has_upstream()? {
+                    println!("Pushing the current branch...");
+                    git::push_current_branch()?;
+                }
+
+                let url = forge.create_pull_request(
+                    &draft.title,
+                    &draft.description,
+                    base_branch,
+                    &pr_options,
+                )?;
+                if let Some(url) = url {
+                    println!("Pull request created: {}", url);
+                }
+                return Ok(());
+            }
+            Next::Cancel => {
+                // Cancel the PR creation process
+                println!("PR creation canceled.");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A generated pull request title and description.
+#[derive(Debug, Clone, PartialEq)]
+struct Draft {
+    title: String,
+    description: String,
+}
+
+impl Draft {
+    /// Reads a draft back from the editor, where the first line is the title.
+    fn parse(text: &str) -> Draft {
+        let (title, description) = text.trim().split_once('\n').unwrap_or((text.trim(), ""));
+        Draft {
+            title: title.trim().to_string(),
+            description: description.trim().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Draft {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n\n{}", self.title, self.description)
+    }
+}
+
+/// Looks up the repository's pull request templates and lets the user pick one
+/// when there are several, unless the settings name one.
+fn select_pr_template(settings: &Settings) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let root = git::get_repo_root()?;
+
+    if let Some(path) = &settings.pr_template {
+        let path = template::resolve_template_path(&root, path)?;
+        return Ok(Some(template::read_template(&path)?));
+    }
+    let mut templates = template::find_templates(&root);
+
+    let path = match templates.len() {
+        0 => return Ok(None),
+        1 => templates.remove(0),
+        _ => {
+            let names: Vec<String> = templates
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(&root)
+                        .unwrap_or(path)
+                        .display()
+                        .to_string()
+                })
+                .collect();
+            let selected = Select::new("Choose a pull request template:", names).raw_prompt()?;
+            templates.remove(selected.index)
+        }
+    };
+
+    Ok(Some(template::read_template(&path)?))
+}
+
+/// Regenerates the title and description of the open pull request for the
+/// current branch and applies them after showing what changed.
+fn update_pull_request(
+    client: &Client,
+    config: &Config,
+    settings: &Settings,
+    prefix: Option<&String>,
+    skip_confirmation: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let forge = Forge::detect(config)?;
+    if forge != Forge::GitHub {
+        return Err(format!("Updating pull requests is not supported on {}", forge).into());
+    }
+
+    let pull_request = gh::view_pull_request(None)?;
+    let base = pull_request.base_ref_name.as_str();
+    println!(
+        "Updating pull request #{}: {}",
+        pull_request.number, pull_request.url
+    );
+
+    let branch_diff = match git::get_merge_base_diff(base, &settings.ignore) {
+        Ok(branch_diff) => branch_diff,
+        Err(GitError::EmptyDiff) => {
+            eprintln!("Error: {}", GitError::EmptyDiff);
+            return Ok(());
+        }
+        Err(e) => return Err(Box::new(e)),
+    };
+    let commits = git::get_branch_commits(Some(base))?;
+    let template = select_pr_template(settings)?;
+
+    let title = client.generate_pr_title(&branch_diff, &commits, prefix, &[])?;
+    let description =
+        client.generate_pr_description(&branch_diff, &commits, template.as_deref(), &[])?;
+    let description = preserve::merge_preserved_sections(&pull_request.body, &description);
+
+    if title == pull_request.title && description == pull_request.body {
+        println!("The pull request is already up to date.");
+        return Ok(());
+    }
+
+    println!(
+        "\nTitle:\n{}",
+        git::diff_texts(&pull_request.title, &title)?
+    );
+    println!(
+        "Description:\n{}",
+        git::diff_texts(&pull_request.body, &description)?
+    );
+
+    let apply = skip_confirmation
+        || Confirm::new("Apply these changes to the pull request?")
+            .with_default(true)
+            .prompt()?;
+
+    if apply {
+        gh::edit_pull_request(pull_request.number, &title, &description)?;
+        println!("Pull request updated: {}", pull_request.url);
+    } else {
+        println!("Pull request update canceled.");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draft_round_trips_through_the_editor() {
+        let draft = Draft {
+            title: "Add login".to_string(),
+            description: "Adds a login form.\n\n- Validates input".to_string(),
+        };
+
+        assert_eq!(Draft::parse(&draft.to_string()), draft);
+        assert_eq!(
+            Draft::parse("  Only a title\n"),
+            Draft {
+                title: "Only a title".to_string(),
+                description: String::new(),
+            }
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use crate::changelog::{self, Section};
+use crate::cli::ReleaseNotesArgs;
+use crate::client::{extract_json, Client, CommitMessageGenerator};
+use crate::commands::{load_config, load_settings, split_range};
+use crate::config::Task;
+use crate::gh;
+use crate::git::{self, LogEntry};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+/// How many of the latest merged pull requests to look through for the ones
+/// in the range.
+const PULL_REQUEST_LIMIT: u32 = 500;
+
+/// A line of the release notes, from a commit or a merged pull request.
+#[derive(Debug, Clone, PartialEq)]
+struct Change {
+    title: String,
+    pull_request: Option<u64>,
+}
+
+pub fn run(
+    args: &ReleaseNotesArgs,
+    profile: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (from, to) = split_range(&args.range)
+        .ok_or_else(|| format!("'{}' is not a range, e.g. v1.0.0..v1.1.0", args.range))?;
+
+    let log = git::get_log(&from, &to)?;
+    let pull_requests = find_pull_requests(&log);
+    // The commits a merged pull request brought in are described by its title
+    let mut covered = HashSet::new();
+    for entry in &log {
+        if let [first, second] = entry.parents.as_slice() {
+            if pull_requests.contains_key(&entry.commit) {
+                covered.extend(git::resolve_commits(&format!("{}..{}", first, second))?);
+            }
+        }
+    }
+    let changes = collect_changes(&log, &pull_requests, &covered);
+    if changes.is_empty() {
+        println!("No changes in '{}'.", args.range);
+        return Ok(());
+    }
+
+    let mut sections: Vec<Option<Section>> = Vec::new();
+    let mut titles = Vec::new();
+    let mut unsorted = Vec::new();
+    for (index, change) in changes.iter().enumerate() {
+        match changelog::parse_conventional(&change.title) {
+            Some((section, description)) => {
+                sections.push(section);
+                titles.push(capitalize(description));
+            }
+            None => {
+                sections.push(None);
+                titles.push(change.title.clone());
+                unsorted.push(index);
+            }
+        }
+    }
+
+    if !unsorted.is_empty() {
+        let config = load_config();
+        let settings = load_settings(&config)?;
+        let (_, service_config, provider) = config.resolve_profile(profile, Task::Commit)?;
+        let client = Client::new(&service_config, provider)?
+            .with_instructions(settings.prompt_instructions());
+
+        eprintln!("Sorting {} change(s) into sections...", unsorted.len());
+        let numbered = unsorted
+            .iter()
+            .enumerate()
+            .map(|(number, &index)| format!("{}. {}", number + 1, titles[index]))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let answer = client.classify_changes(&numbered)?;
+        for (&index, section) in unsorted
+            .iter()
+            .zip(parse_sections(&answer, unsorted.len())?)
+        {
+            sections[index] = section;
+        }
+    }
+
+    let notes: Vec<(Section, String)> = changes
+        .iter()
+        .zip(titles)
+        .zip(sections)
+        .filter_map(|((change, title), section)| {
+            let title = match change.pull_request {
+                Some(number) => format!("{} (#{})", title, number),
+                None => title,
+            };
+            section.map(|section| (section, title))
+        })
+        .collect();
+    if notes.is_empty() {
+        println!("No notable changes in '{}'.", args.range);
+        return Ok(());
+    }
+
+    let version = args.version.clone().or_else(|| version_from_tag(&to));
+    let heading = match version {
+        Some(version) => format!("[{}] - {}", version, git::get_commit_date(&to)?),
+        None => "[Unreleased]".to_string(),
+    };
+    let entry = changelog::render_entry(&heading, &notes);
+
+    match &args.changelog {
+        Some(path) => {
+            let path = match path {
+                Some(path) => path.clone(),
+                None => git::get_repo_root()?.join("CHANGELOG.md"),
+            };
+            let existing = fs::read_to_string(&path).ok();
+            fs::write(&path, changelog::prepend_entry(existing.as_deref(), &entry))?;
+            println!("Added the release notes to {}", path.display());
+        }
+        None => print!("{}", entry),
+    }
+
+    Ok(())
+}
+
+/// Maps the merge commits in the log to the pull requests they merged. Without
+/// gh, or outside of GitHub, the commits alone are used.
+fn find_pull_requests(log: &[LogEntry]) -> HashMap<String, (u64, String)> {
+    let Ok(pull_requests) = gh::merged_pull_requests(PULL_REQUEST_LIMIT) else {
+        return HashMap::new();
+    };
+
+    let commits: HashSet<&str> = log.iter().map(|entry| entry.commit.as_str()).collect();
+    pull_requests
+        .into_iter()
+        .filter_map(|pull_request| {
+            let commit = pull_request.merge_commit()?.to_string();
+            commits
+                .contains(commit.as_str())
+                .then_some((commit, (pull_request.number, pull_request.title)))
+        })
+        .collect()
+}
+
+/// Lists the changes in the log, oldest first. A merged pull request stands in
+/// for its merge commit and the commits it brought in; other merges are left out.
+fn collect_changes(
+    log: &[LogEntry],
+    pull_requests: &HashMap<String, (u64, String)>,
+    covered: &HashSet<String>,
+) -> Vec<Change> {
+    log.iter()
+        .filter_map(|entry| {
+            if let Some((number, title)) = pull_requests.get(&entry.commit) {
+                Some(Change {
+                    title: title.clone(),
+                    pull_request: Some(*number),
+                })
+            } else if entry.parents.len() > 1 || covered.contains(&entry.commit) {
+                None
+            } else {
+                Some(Change {
+                    title: entry.subject.clone(),
+                    pull_request: None,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Reads the model's `{"1": "Added"}` answer for `count` changes. Changes it
+/// puts in Internal are left out, and ones it skipped go to Changed.
+fn parse_sections(
+    answer: &str,
+    count: usize,
+) -> Result<Vec<Option<Section>>, Box<dyn std::error::Error>> {
+    let sections: HashMap<String, String> = extract_json(answer, "classification")?;
+
+    Ok((1..=count)
+        .map(|number| match sections.get(&number.to_string()) {
+            Some(name) => Section::from_name(name),
+            None => Some(Section::Changed),
+        })
+        .collect())
+}
+
+/// Reads the version from a tag such as `v1.2.0`.
+fn version_from_tag(revision: &str) -> Option<String> {
+    if !git::is_tag(revision) {
+        return None;
+    }
+    let version = revision.strip_prefix('v').unwrap_or(revision);
+    Some(version.to_string())
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(commit: &str, parents: &[&str], subject: &str) -> LogEntry {
+        LogEntry {
+            commit: commit.to_string(),
+            parents: parents.iter().map(|parent| parent.to_string()).collect(),
+            subject: subject.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_collect_changes_uses_pull_request_titles() {
+        let log = vec![
+            entry("b1", &["a"], "wip"),
+            entry("b2", &["b1"], "address review"),
+            entry("m", &["a", "b2"], "Merge pull request #7 from me/login"),
+            entry("s", &["m"], "fix: handle empty diffs (#8)"),
+            entry("d", &["s"], "Update README"),
+            entry("m2", &["d", "x"], "Merge branch 'main'"),
+        ];
+        let pull_requests = HashMap::from([
+            ("m".to_string(), (7, "Add login".to_string())),
+            ("s".to_string(), (8, "fix: handle empty diffs".to_string())),
+        ]);
+        let covered = HashSet::from(["b1".to_string(), "b2".to_string()]);
+
+        assert_eq!(
+            collect_changes(&log, &pull_requests, &covered),
+            vec![
+                Change {
+                    title: "Add login".to_string(),
+                    pull_request: Some(7),
+                },
+                Change {
+                    title: "fix: handle empty diffs".to_string(),
+                    pull_request: Some(8),
+                },
+                Change {
+                    title: "Update README".to_string(),
+                    pull_request: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_sections() {
+        let answer = "```json\n{\"1\": \"Added\", \"2\": \"internal\", \"4\": \"Nonsense\"}\n```";
+
+        assert_eq!(
+            parse_sections(answer, 4).unwrap(),
+            vec![Some(Section::Added), None, Some(Section::Changed), None]
+        );
+        assert!(parse_sections("Added, Fixed", 2).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::cli::RewordArgs;
+use crate::client::{Client, CommitMessageGenerator};
+use crate::commands::{load_config, load_settings, resolve_prefix};
+use crate::config::Task;
+use crate::git::{self, GitError};
+use crate::review::{self, Action, Next, Review};
+use crate::vim::Vim;
+
+pub fn run(args: &RewordArgs, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let commits = git::resolve_commits(&args.revision)?;
+    if commits.is_empty() {
+        println!("No commits in '{}'.", args.revision);
+        return Ok(());
+    }
+
+    let head = git::rev_parse("HEAD")?;
+    for commit in &commits {
+        if !git::is_ancestor(commit, &head)? {
+            return Err(format!("Commit {} is not on the current branch", commit).into());
+        }
+    }
+    // Check before generating anything, the rebase would refuse later
+    let amend_only = commits == [head.clone()];
+    if !amend_only && git::has_uncommitted_changes()? {
+        return Err("The working tree has uncommitted changes, commit or stash them first".into());
+    }
+
+    let config = load_config();
+    let settings = load_settings(&config)?;
+    let (_, service_config, provider) = config.resolve_profile(profile, Task::Commit)?;
+    let client =
+        Client::new(&service_config, provider)?.with_instructions(settings.prompt_instructions());
+    let prefix = resolve_prefix(args.prefix.as_ref(), &settings)?;
+    let prefix = prefix.as_ref();
+
+    let mut messages = Vec::new();
+    for (index, commit) in commits.iter().enumerate() {
+        let original = git::get_commit_message(commit)?;
+        println!(
+            "\nCommit {} of {}: {}\n{}\n\nCurrent message:\n\n{}",
+            index + 1,
+            commits.len(),
+            &commit[..commit.len().min(12)],
+            git::get_commit_stat(commit)?,
+            original
+        );
+
+        let diff = match git::get_commit_diff(commit, &settings.ignore) {
+            Ok(diff) => diff,
+            Err(GitError::EmptyDiff) => {
+                println!("\nThe commit has no changes to describe, keeping its message.");
+                continue;
+            }
+            Err(e) => return Err(Box::new(e)),
+        };
+
+        match review_message(&client, &diff, prefix)? {
+            Outcome::Reword(message) if message != original => {
+                messages.push((commit.clone(), message))
+            }
+            Outcome::Reword(_) | Outcome::Keep => println!("Keeping the current message."),
+            Outcome::Cancel => {
+                println!("Reword canceled. No commits were changed.");
+                return Ok(());
+            }
+        }
+    }
+
+    if messages.is_empty() {
+        println!("\nNothing to reword.");
+        return Ok(());
+    }
+
+    if amend_only {
+        git::amend_message(&messages[0].1)?;
+    } else {
+        git::reword_commits(&messages)?;
+    }
+    println!("\nReworded {} commit(s).", messages.len());
+
+    Ok(())
+}
+
+enum Outcome {
+    Reword(String),
+    Keep,
+    Cancel,
+}
+
+/// Reviews generated messages for one commit.
+fn review_message(
+    client: &Client,
+    diff: &str,
+    prefix: Option<&String>,
+) -> Result<Outcome, Box<dyn std::error::Error>> {
+    let mut review = Review::new();
+    review.add_candidates(client.generate_commit_messages(diff, prefix, 1, &[])?);
+
+    loop {
+        println!("\nGenerated commit message:\n\n{}\n", review.current());
+
+        let mut actions = Action::available(review.candidates().len() > 1);
+        actions.insert(actions.len() - 1, Action::Skip);
+
+        let action = review::prompt_action(actions)?;
+        if action == Action::Skip {
+            return Ok(Outcome::Keep);
+        }
+        let event = review.event_for(
+            action,
+            |message| Vim::new().edit_message(message),
+            |message| review::first_line(message),
+        )?;
+
+        match review.handle(event) {
+            Next::Prompt => {}
+            Next::Generate => {
+                println!("Generating commit message...");
+                let feedback = review.feedback(|message| message);
+                review.add_candidates(client.generate_commit_messages(diff, prefix, 1, &feedback)?);
+            }
+            Next::Finish(message) => return Ok(Outcome::Reword(message)),
+            Next::Cancel => return Ok(Outcome::Cancel),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use crate::cli::SplitArgs;
+use crate::client::{apply_prefix, extract_json, Client, CommitMessageGenerator, Feedback};
+use crate::commands::{load_config, load_settings, resolve_prefix};
+use crate::config::Task;
+use crate::git::{self, GitError};
+use crate::patch::{Patch, Unit};
+use crate::review::{self, Action, Next, Review};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// A commit in a plan, with the hunks it takes by their position in the diff.
+#[derive(Debug, Clone, PartialEq)]
+struct Group {
+    message: String,
+    hunks: Vec<usize>,
+}
+
+/// A proposed split, with the model's answer kept for feedback rounds.
+#[derive(Debug, Clone, PartialEq)]
+struct Plan {
+    raw: String,
+    groups: Vec<Group>,
+}
+
+#[derive(Deserialize)]
+struct PlanResponse {
+    commits: Vec<PlannedCommit>,
+}
+
+#[derive(Deserialize)]
+struct PlannedCommit {
+    message: String,
+    #[serde(default)]
+    hunks: Vec<usize>,
+}
+
+pub fn run(args: &SplitArgs, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config();
+    let settings = load_settings(&config)?;
+    let (_, service_config, provider) = config.resolve_profile(profile, Task::Commit)?;
+    let client =
+        Client::new(&service_config, provider)?.with_instructions(settings.prompt_instructions());
+    let prefix = resolve_prefix(args.prefix.as_ref(), &settings)?;
+    let prefix = prefix.as_ref();
+
+    let patch = match git::get_staged_patch() {
+        Ok(patch) => Patch::parse(&patch),
+        Err(GitError::EmptyDiff) => {
+            eprintln!("Error: {}", GitError::EmptyDiff);
+            return Ok(());
+        }
+        Err(e) => return Err(Box::new(e)),
+    };
+    let units = patch.units();
+    let shown: HashSet<String> = git::get_staged_files(&settings.ignore)?
+        .into_iter()
+        .collect();
+    let hunks = describe_hunks(&patch, &units, &shown);
+    println!("{}", git::get_diff_stat(&["--staged"], &settings.ignore)?);
+
+    println!("Planning commits for {} hunk(s)...", units.len());
+    let mut review = Review::new();
+    review.add_candidates(vec![generate_plan(
+        &client,
+        &hunks,
+        units.len(),
+        prefix,
+        &[],
+    )?]);
+
+    let plan = loop {
+        print_plan(review.current(), &patch, &units);
+
+        let actions = vec![
+            Action::Accept,
+            Action::Feedback,
+            Action::Regenerate,
+            Action::Quit,
+        ];
+        let action = review::prompt_action(actions)?;
+        let event = review.event_for(
+            action,
+            |_| Err("A plan can't be edited, give feedback instead".into()),
+            |plan| format!("{} commit(s)", plan.groups.len()),
+        )?;
+
+        match review.handle(event) {
+            Next::Prompt => {}
+            Next::Generate => {
+                println!("Planning commits...");
+                let feedback = review.feedback(|plan| &plan.raw);
+                review.add_candidates(vec![generate_plan(
+                    &client,
+                    &hunks,
+                    units.len(),
+                    prefix,
+                    &feedback,
+                )?]);
+            }
+            Next::Finish(plan) => break plan,
+            Next::Cancel => {
+                println!("Split canceled. Nothing was committed.");
+                return Ok(());
+            }
+        }
+    };
+
+    let groups: Vec<Vec<Unit>> = plan
+        .groups
+        .iter()
+        .map(|group| group.hunks.iter().map(|&hunk| units[hunk]).collect())
+        .collect();
+    let patches = patch.split(&groups);
+
+    // Stage every patch into a scratch index first, so a plan git can't apply
+    // fails before the real index is touched
+    let scratch = tempfile::tempdir()?;
+    let scratch_index = scratch.path().join("index");
+    git::reset_index(Some(&scratch_index))?;
+    for patch in &patches {
+        git::apply_cached(patch, Some(&scratch_index))?;
+    }
+
+    git::reset_index(None)?;
+    for (index, (group, patch)) in plan.groups.iter().zip(&patches).enumerate() {
+        git::apply_cached(patch, None)?;
+        if let Err(e) = git::commit(&group.message) {
+            // Put the rest back so the staged changes aren't lost
+            for patch in &patches[index + 1..] {
+                git::apply_cached(patch, None)?;
+            }
+            eprintln!(
+                "Committed {} of {} commits, the rest of the changes are staged again.",
+                index,
+                patches.len()
+            );
+            return Err(Box::new(e));
+        }
+    }
+    println!("\nCreated {} commit(s).", patches.len());
+
+    Ok(())
+}
+
+fn generate_plan(
+    client: &Client,
+    hunks: &str,
+    count: usize,
+    prefix: Option<&String>,
+    feedback: &[Feedback],
+) -> Result<Plan, Box<dyn std::error::Error>> {
+    let raw = client.generate_commit_plan(hunks, feedback)?;
+    let groups = parse_plan(&raw, count, prefix)?;
+    Ok(Plan { raw, groups })
+}
+
+/// Numbers the hunks for the model, leaving out the content of ignored files.
+fn describe_hunks(patch: &Patch, units: &[Unit], shown: &HashSet<String>) -> String {
+    units
+        .iter()
+        .enumerate()
+        .map(|(index, &unit)| {
+            if shown.contains(patch.path(unit)) {
+                format!(
+                    "Hunk {}: {}\n{}",
+                    index + 1,
+                    patch.path(unit),
+                    patch.text(unit)
+                )
+            } else {
+                format!(
+                    "Hunk {}: {} (changes not shown)\n",
+                    index + 1,
+                    patch.path(unit)
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reads a plan from the model's answer. Hunks are numbered from 1 in the answer
+/// and from 0 in the plan. Unknown and repeated hunks are dropped and hunks the
+/// answer leaves out go to the last commit, so every hunk is committed once.
+fn parse_plan(
+    raw: &str,
+    count: usize,
+    prefix: Option<&String>,
+) -> Result<Vec<Group>, Box<dyn std::error::Error>> {
+    let response: PlanResponse = extract_json(raw, "plan")?;
+
+    let mut assigned = HashSet::new();
+    let mut groups: Vec<Group> = response
+        .commits
+        .into_iter()
+        .filter(|commit| !commit.message.trim().is_empty())
+        .map(|commit| {
+            let mut hunks: Vec<usize> = commit
+                .hunks
+                .into_iter()
+                .filter(|&hunk| (1..=count).contains(&hunk) && assigned.insert(hunk))
+                .map(|hunk| hunk - 1)
+                .collect();
+            hunks.sort();
+            Group {
+                message: apply_prefix(&commit.message, prefix),
+                hunks,
+            }
+        })
+        .filter(|group| !group.hunks.is_empty())
+        .collect();
+
+    let Some(last) = groups.last_mut() else {
+        return Err("The plan has no commits".into());
+    };
+    last.hunks.extend(
+        (1..=count)
+            .filter(|hunk| !assigned.contains(hunk))
+            .map(|hunk| hunk - 1),
+    );
+    last.hunks.sort();
+
+    Ok(groups)
+}
+
+fn print_plan(plan: &Plan, patch: &Patch, units: &[Unit]) {
+    println!("\nProposed commits:");
+    for (index, group) in plan.groups.iter().enumerate() {
+        println!("\n{}. {}", index + 1, group.message.replace('\n', "\n   "));
+        for &hunk in &group.hunks {
+            println!("     {}", patch.describe(units[hunk]));
+        }
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plan_converts_hunk_numbers() {
+        let raw = r#"{"commits": [
+            {"message": "Add login form", "hunks": [3, 1]},
+            {"message": "Fix typo in README", "hunks": [2]}
+        ]}"#;
+
+        assert_eq!(
+            parse_plan(raw, 3, None).unwrap(),
+            vec![
+                Group {
+                    message: "Add login form".to_string(),
+                    hunks: vec![0, 2],
+                },
+                Group {
+                    message: "Fix typo in README".to_string(),
+                    hunks: vec![1],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_plan_commits_every_hunk_once() {
+        let raw = "Here is the plan:\n```json\n{\"commits\": [\
+            {\"message\": \"Add login form\", \"hunks\": [1, 2]},\
+            {\"message\": \"Update login form\", \"hunks\": [2, 9]},\
+            {\"message\": \"Fix typo\", \"hunks\": [3]}\
+        ]}\n```";
+
+        let groups = parse_plan(raw, 4, None).unwrap();
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].hunks, vec![0, 1]);
+        assert_eq!(groups[1].message, "Fix typo");
+        assert_eq!(groups[1].hunks, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_parse_plan_applies_prefix() {
+        let raw = r#"{"commits": [{"message": "Add login form", "hunks": [1]}]}"#;
+
+        let groups = parse_plan(raw, 1, Some(&"TICKET-1".to_string())).unwrap();
+
+        assert_eq!(groups[0].message, "TICKET-1 Add login form");
+    }
+
+    #[test]
+    fn test_parse_plan_rejects_answers_without_commits() {
+        assert!(parse_plan("I can't split these changes.", 2, None).is_err());
+        assert!(parse_plan(r#"{"commits": []}"#, 2, None).is_err());
+    }
+}
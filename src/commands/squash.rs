@@ -0,0 +1,216 @@
+use crate::cli::SquashArgs;
+use crate::client::{Client, CommitMessageGenerator};
+use crate::commands::{load_config, load_settings, resolve_prefix, split_range};
+use crate::config::Task;
+use crate::gh::{self, PullRequest};
+use crate::git::{self, GitError};
+use crate::review::{self, Action, Next, Review};
+use crate::vim::Vim;
+use std::fs;
+use std::path::PathBuf;
+
+/// Where the squashed commits come from, and so where the message goes.
+enum Source {
+    /// A range of commits, the message is printed.
+    Range(String, String),
+    /// A `git merge` waiting to be committed, the message goes to `MERGE_MSG`.
+    Merge(PathBuf),
+    /// A `git merge --squash` waiting to be committed, the message goes to `SQUASH_MSG`.
+    Squash(PathBuf),
+    /// A pull request that is squash-merged with gh.
+    PullRequest(PullRequest),
+}
+
+pub fn run(args: &SquashArgs, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let source = find_source(args)?;
+
+    let config = load_config();
+    let settings = load_settings(&config)?;
+    let (_, service_config, provider) = config.resolve_profile(profile, Task::Commit)?;
+    let client =
+        Client::new(&service_config, provider)?.with_instructions(settings.prompt_instructions());
+    let prefix = resolve_prefix(args.prefix.as_ref(), &settings)?;
+    let prefix = prefix.as_ref();
+
+    let diff = match &source {
+        Source::Range(from, to) => git::get_range_diff(from, to, &settings.ignore),
+        Source::Merge(_) | Source::Squash(_) => git::get_diff(&settings.ignore),
+        Source::PullRequest(pull_request) => match gh::pull_request_diff(pull_request.number)? {
+            diff if diff.is_empty() => Err(GitError::EmptyDiff),
+            diff => Ok(diff),
+        },
+    };
+    let diff = match diff {
+        Ok(diff) => diff,
+        Err(GitError::EmptyDiff) => {
+            eprintln!("Error: There are no changes to squash");
+            return Ok(());
+        }
+        Err(e) => return Err(Box::new(e)),
+    };
+    let commits = match &source {
+        Source::Range(from, to) => git::get_commits(from, to)?,
+        Source::Merge(_) => git::get_commits("HEAD", "MERGE_HEAD")?,
+        Source::Squash(path) => parse_squash_msg(&fs::read_to_string(path)?),
+        Source::PullRequest(pull_request) => gh::pull_request_commits(pull_request.number)?,
+    };
+
+    let mut review = Review::new();
+    review.add_candidates(vec![client.generate_squash_message(
+        &diff,
+        &commits,
+        prefix,
+        &[],
+    )?]);
+
+    let message = loop {
+        println!("\nGenerated squash message:\n\n{}\n", review.current());
+
+        let action = review::prompt_action(Action::available(review.candidates().len() > 1))?;
+        let event = review.event_for(
+            action,
+            |message| Vim::new().edit_message(message),
+            |message| review::first_line(message),
+        )?;
+
+        match review.handle(event) {
+            Next::Prompt => {}
+            Next::Generate => {
+                println!("Generating squash message...");
+                let feedback = review.feedback(|message| message);
+                let message = client.generate_squash_message(&diff, &commits, prefix, &feedback)?;
+                review.add_candidates(vec![message]);
+            }
+            Next::Finish(message) => break message,
+            Next::Cancel => {
+                println!("Squash message discarded.");
+                return Ok(());
+            }
+        }
+    };
+
+    match source {
+        Source::Range(..) => println!("{}", message),
+        Source::Merge(path) | Source::Squash(path) => {
+            let existing = fs::read_to_string(&path)?;
+            fs::write(&path, replace_message(&existing, &message))?;
+            println!(
+                "Wrote the message to {}. Run 'git commit' to use it.",
+                path.display()
+            );
+        }
+        Source::PullRequest(pull_request) => {
+            let (subject, body) = message.split_once('\n').unwrap_or((&message, ""));
+            gh::merge_pull_request(pull_request.number, subject.trim(), body.trim())?;
+            println!("Pull request merged: {}", pull_request.url);
+        }
+    }
+
+    Ok(())
+}
+
+fn find_source(args: &SquashArgs) -> Result<Source, Box<dyn std::error::Error>> {
+    if let Some(number) = &args.pr {
+        return Ok(Source::PullRequest(gh::view_pull_request(
+            number.as_deref(),
+        )?));
+    }
+    if let Some(range) = &args.range {
+        let (from, to) = split_range(range)
+            .ok_or_else(|| format!("'{}' is not a range, e.g. main..feature", range))?;
+        return Ok(Source::Range(from, to));
+    }
+
+    if git::rev_parse("MERGE_HEAD").is_ok() {
+        return Ok(Source::Merge(git::get_git_path("MERGE_MSG")?));
+    }
+    let squash_msg = git::get_git_path("SQUASH_MSG")?;
+    if squash_msg.exists() {
+        return Ok(Source::Squash(squash_msg));
+    }
+
+    Err("No merge or squash is in progress. Pass a range, e.g. gcmgen squash main..feature, or --pr".into())
+}
+
+/// Reads the commit messages from the log `git merge --squash` writes, in the
+/// same form as `git::get_commits`.
+fn parse_squash_msg(text: &str) -> String {
+    let mut commits: Vec<String> = Vec::new();
+
+    for line in text.lines() {
+        if line.starts_with("commit ") {
+            commits.push(String::new());
+        } else if let (Some(message), Some(commit)) =
+            (line.strip_prefix("    "), commits.last_mut())
+        {
+            commit.push_str(message);
+            commit.push('\n');
+        } else if let (true, Some(commit)) = (line.is_empty(), commits.last_mut()) {
+            if !commit.is_empty() {
+                commit.push('\n');
+            }
+        }
+    }
+
+    // git lists the newest commit first
+    commits
+        .iter()
+        .rev()
+        .map(|commit| format!("{}\n---", commit.trim()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Puts the message in place of git's default, keeping its comments such as
+/// the list of conflicts.
+fn replace_message(existing: &str, message: &str) -> String {
+    let comments: Vec<&str> = existing
+        .lines()
+        .filter(|line| line.starts_with('#'))
+        .collect();
+
+    if comments.is_empty() {
+        format!("{}\n", message.trim_end())
+    } else {
+        format!("{}\n\n{}\n", message.trim_end(), comments.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_squash_msg() {
+        let text = "Squashed commit of the following:\n\n\
+            commit 2222\n\
+            Author: A <a@example.com>\n\
+            Date:   Mon Oct 19 10:00:00 2026 +0200\n\n    \
+            Fix typo\n\n\
+            commit 1111\n\
+            Author: A <a@example.com>\n\
+            Date:   Mon Oct 19 09:00:00 2026 +0200\n\n    \
+            Add login\n    \n    \
+            With a form.\n";
+
+        assert_eq!(
+            parse_squash_msg(text),
+            "Add login\n\nWith a form.\n---\nFix typo\n---"
+        );
+    }
+
+    #[test]
+    fn test_replace_message_keeps_comments() {
+        assert_eq!(
+            replace_message(
+                "Merge branch 'feature'\n\n# Conflicts:\n#\tsrc/main.rs\n",
+                "Add login\n"
+            ),
+            "Add login\n\n# Conflicts:\n#\tsrc/main.rs\n"
+        );
+        assert_eq!(
+            replace_message("Squashed commit of the following:\n", "Add login"),
+            "Add login\n"
+        );
+    }
+}
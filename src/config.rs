@@ -1,48 +1,364 @@
+use crate::keyring;
+use crate::migrate;
+use crate::settings::Settings;
+use crate::toml_file;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::{env, fmt, fs, io};
+use toml_edit::DocumentMut;
 
+/// Where an API token is stored. Plain tokens are kept in the config file as a
+/// string, the other variants only reference the token.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ApiToken {
+    Plain(String),
+    /// An entry in the OS keyring, looked up by account name.
+    Keyring {
+        keyring: String,
+    },
+    /// A shell command that prints the token, e.g. `pass show openai`.
+    Command {
+        command: String,
+    },
+    /// An environment variable holding the token.
+    Env {
+        env: String,
+    },
+}
+
+impl ApiToken {
+    /// Returns the token itself, reading it from where it is stored.
+    pub fn resolve(&self) -> Result<String, io::Error> {
+        match self {
+            ApiToken::Plain(token) => Ok(token.clone()),
+            ApiToken::Keyring { keyring: account } => keyring::get_password(account),
+            ApiToken::Command { command } => {
+                let output = Command::new("sh").args(["-c", command]).output()?;
+                // Password managers like pass keep metadata after the first line
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let token = stdout.lines().next().unwrap_or_default().trim();
+
+                if output.status.success() && !token.is_empty() {
+                    Ok(token.to_string())
+                } else {
+                    Err(io::Error::other(format!(
+                        "Token command '{}' failed: {}",
+                        command,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )))
+                }
+            }
+            ApiToken::Env { env: variable } => env::var(variable).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Environment variable '{}' is not set", variable),
+                )
+            }),
+        }
+    }
+}
+
+impl Display for ApiToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiToken::Plain(token) => write!(f, "{}", mask_token(token)),
+            ApiToken::Keyring { keyring } => write!(f, "keyring ({})", keyring),
+            ApiToken::Command { command } => write!(f, "command ({})", command),
+            ApiToken::Env { env } => write!(f, "environment variable ({})", env),
+        }
+    }
+}
+
+/// The AI providers gcmgen can talk to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Provider {
+    OpenAI,
+    Anthropic,
+}
+
+impl Provider {
+    pub fn all() -> Vec<Provider> {
+        vec![Provider::OpenAI, Provider::Anthropic]
+    }
+
+    pub fn from_name(name: &str) -> Option<Provider> {
+        match name {
+            "OpenAI" => Some(Provider::OpenAI),
+            "Anthropic" => Some(Provider::Anthropic),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Provider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Provider::OpenAI => write!(f, "OpenAI"),
+            Provider::Anthropic => write!(f, "Anthropic"),
+        }
+    }
+}
+
+/// A named profile: a provider with its own token and model. Profiles in older
+/// configs have no provider and are named after it instead.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ServiceConfig {
-    pub api_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<Provider>,
+    pub api_token: ApiToken,
     pub model: String,
 }
 
+impl ServiceConfig {
+    /// Returns the provider of the profile called `name`.
+    pub fn provider(&self, name: &str) -> Option<Provider> {
+        self.provider.or_else(|| Provider::from_name(name))
+    }
+}
+
+/// The tasks that can each use their own profile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Task {
+    Commit,
+    PullRequest,
+}
+
+/// Profiles assigned to tasks, used instead of the default profile.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TaskProfiles {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr: Option<String>,
+}
+
+impl TaskProfiles {
+    pub fn is_empty(&self) -> bool {
+        self.commit.is_none() && self.pr.is_none()
+    }
+
+    pub fn get(&self, task: Task) -> Option<&String> {
+        match task {
+            Task::Commit => self.commit.as_ref(),
+            Task::PullRequest => self.pr.as_ref(),
+        }
+    }
+}
+
+/// Forges that gcmgen talks to through their REST API instead of a CLI.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ForgeKind {
+    Forgejo,
+    Bitbucket,
+}
+
+impl Display for ForgeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ForgeKind::Forgejo => write!(f, "Forgejo"),
+            ForgeKind::Bitbucket => write!(f, "Bitbucket"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ForgeConfig {
+    pub kind: ForgeKind,
+    /// Base URL of the forge, e.g. `https://codeberg.org`. Remotes on the same
+    /// host use this forge.
+    pub base_url: String,
+    pub api_token: ApiToken,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
+    /// Version of the config format, see [`crate::migrate`].
+    #[serde(default)]
+    pub version: u32,
     pub default_service: String,
+    /// Profiles by name.
     pub services: HashMap<String, ServiceConfig>,
+    #[serde(default, skip_serializing_if = "TaskProfiles::is_empty")]
+    pub task_profiles: TaskProfiles,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub forges: HashMap<String, ForgeConfig>,
+    /// User-wide defaults, overridden by the repository's `.gcmgen.toml`.
+    #[serde(default, skip_serializing_if = "Settings::is_empty")]
+    pub settings: Settings,
+    /// Environment overrides for the resolved profile, never saved.
+    #[serde(skip)]
+    pub(crate) env: EnvOverrides,
+}
+
+/// The environment variables that override the profile a command uses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct EnvOverrides {
+    /// `GCMGEN_SERVICE`, the profile to use.
+    service: Option<String>,
+    /// Whether `GCMGEN_API_KEY` is set.
+    api_key: bool,
+    /// `GCMGEN_MODEL`, the model to use.
+    model: Option<String>,
+    /// The providers whose key variable is set, such as `OPENAI_API_KEY`.
+    provider_keys: Vec<(Provider, &'static str)>,
+}
+
+impl EnvOverrides {
+    /// Applies the overrides to a profile of `provider`.
+    fn apply(&self, service_config: &mut ServiceConfig, provider: Provider) {
+        if let Some((_, key_var)) = self.provider_keys.iter().find(|(p, _)| *p == provider) {
+            service_config.api_token = ApiToken::Env {
+                env: key_var.to_string(),
+            };
+        }
+        if self.api_key {
+            service_config.api_token = ApiToken::Env {
+                env: "GCMGEN_API_KEY".to_string(),
+            };
+        }
+        if let Some(model) = &self.model {
+            service_config.model = model.clone();
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: migrate::CURRENT_VERSION,
+            default_service: String::new(),
+            services: HashMap::new(),
+            task_profiles: TaskProfiles::default(),
+            forges: HashMap::new(),
+            settings: Settings::default(),
+            env: EnvOverrides::default(),
+        }
+    }
 }
 
 impl Display for Config {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         writeln!(f, "Default Service: {}", self.default_service)?;
+        if let Some(profile) = &self.task_profiles.commit {
+            writeln!(f, "Commit Profile: {}", profile)?;
+        }
+        if let Some(profile) = &self.task_profiles.pr {
+            writeln!(f, "PR Profile: {}", profile)?;
+        }
 
         for (service_name, service_config) in &self.services {
             writeln!(f, "\nService: {}", service_name)?;
+            if let Some(provider) = service_config.provider(service_name) {
+                writeln!(f, "Provider: {}", provider)?;
+            }
             writeln!(f, "{}", service_config)?;
         }
 
+        for (forge_name, forge_config) in &self.forges {
+            writeln!(f, "\nForge: {}", forge_name)?;
+            writeln!(f, "{}", forge_config)?;
+        }
+
         Ok(())
     }
 }
 
 impl Display for ServiceConfig {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let masked_token = if self.api_token.len() > 5 {
-            format!("{}***", &self.api_token[..5])
-        } else {
-            format!("{}***", self.api_token)
-        };
-
-        writeln!(f, "API Token: {}", masked_token)?;
+        writeln!(f, "API Token: {}", self.api_token)?;
         writeln!(f, "Model: {}", self.model)
     }
 }
 
+impl Display for ForgeConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Kind: {}", self.kind)?;
+        writeln!(f, "Base URL: {}", self.base_url)?;
+        writeln!(f, "API Token: {}", self.api_token)
+    }
+}
+
+/// Turns a dotted key into a JSON pointer.
+fn key_to_pointer(key: &str) -> String {
+    key.split('.')
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+pub fn mask_token(token: &str) -> String {
+    if token.len() > 5 {
+        format!("{}***", &token[..5])
+    } else {
+        format!("{}***", token)
+    }
+}
+
+/// Masks every plaintext token in a config value, such as a whole service or
+/// all of them.
+pub fn mask_tokens(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(token) if key == "api_token" => *token = mask_token(token),
+                    value => mask_tokens(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(mask_tokens),
+        _ => {}
+    }
+}
+
 static CONFIG_DIRECTORY: &str = "gcmgen";
+static CONFIG_FILE: &str = "config.toml";
+/// The config file used before the switch to TOML, still read until converted.
+static LEGACY_CONFIG_FILE: &str = "config.json";
+/// Marks that the user declined converting `config.json` to TOML.
+static KEEP_JSON_MARKER: &str = ".keep-config-json";
+
+/// The environment variable that holds the API key of each provider.
+static PROVIDER_KEY_VARS: [(Provider, &str); 2] = [
+    (Provider::OpenAI, "OPENAI_API_KEY"),
+    (Provider::Anthropic, "ANTHROPIC_API_KEY"),
+];
+
+/// Returns the model used for a service when none is configured.
+pub fn default_model(service: &str) -> Option<&'static str> {
+    match service {
+        "OpenAI" => Some("gpt-4o-mini"),
+        "Anthropic" => Some("claude-3-5-sonnet-20240620"),
+        _ => None,
+    }
+}
+
+/// Writes `contents` to `path`, readable and writable only by the owner since
+/// the file may hold plaintext tokens.
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> Result<(), io::Error> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // The mode only applies to new files, so tighten existing ones too
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> Result<(), io::Error> {
+    fs::write(path, contents)
+}
 
 impl Config {
     fn get_config_dir() -> PathBuf {
@@ -55,36 +371,343 @@ impl Config {
         base_dir.join(CONFIG_DIRECTORY)
     }
 
-    pub fn save(&self) -> Result<(), io::Error> {
+    /// Returns the path of the config file: `config.toml`, unless only a
+    /// `config.json` from an older version exists.
+    pub fn get_config_file() -> PathBuf {
         let config_dir = Self::get_config_dir();
-        let config_file = config_dir.join("config.json");
+        let legacy_file = config_dir.join(LEGACY_CONFIG_FILE);
+
+        if legacy_file.exists() && !config_dir.join(CONFIG_FILE).exists() {
+            legacy_file
+        } else {
+            config_dir.join(CONFIG_FILE)
+        }
+    }
 
-        let config_json = serde_json::to_string_pretty(&self)?;
-        fs::create_dir_all(config_dir)?;
-        fs::write(config_file, config_json)?;
+    /// Whether the config still lives in a `config.json` that can be converted.
+    pub fn uses_legacy_json() -> bool {
+        Self::get_config_file().ends_with(LEGACY_CONFIG_FILE)
+    }
 
-        Ok(())
+    pub fn save(&self) -> Result<(), io::Error> {
+        let config_file = Self::get_config_file();
+        fs::create_dir_all(Self::get_config_dir())?;
+
+        let contents = if config_file.ends_with(LEGACY_CONFIG_FILE) {
+            serde_json::to_string_pretty(&self)?
+        } else {
+            let new_document = toml_file::to_document(self)?;
+
+            match fs::read_to_string(&config_file) {
+                Ok(existing) => {
+                    let mut document: DocumentMut = existing
+                        .parse()
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    toml_file::update_document(&mut document, &new_document);
+                    document.to_string()
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    format!("{}{}", toml_file::CONFIG_HEADER, new_document)
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        write_private(&config_file, &contents)
     }
 
     pub fn load() -> Result<Self, io::Error> {
-        let config_dir = Self::get_config_dir();
-        let config_file = config_dir.join("config.json");
+        let config_file = Self::get_config_file();
+
+        let config_data = fs::read_to_string(&config_file)?;
+        let is_json = config_file.ends_with(LEGACY_CONFIG_FILE);
+        let mut value: Value = if is_json {
+            serde_json::from_str(&config_data)?
+        } else {
+            toml::from_str(&config_data).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid {}: {}", config_file.display(), e),
+                )
+            })?
+        };
+
+        let migrated_from = migrate::migrate(&mut value)?;
+        let config: Config = serde_json::from_value(value)?;
 
-        let config_data = fs::read_to_string(config_file)?;
-        let config: Config = serde_json::from_str(&config_data)?;
+        if let Some(version) = migrated_from {
+            // Keep the old file around in case the migration lost something
+            let extension = if is_json { "json" } else { "toml" };
+            let backup =
+                Self::get_config_dir().join(format!("config.v{}.{}.bak", version, extension));
+            write_private(&backup, &config_data)?;
+            config.save()?;
+            eprintln!(
+                "Upgraded the config file to version {}. The old file was saved to {}.",
+                migrate::CURRENT_VERSION,
+                backup.display()
+            );
+        }
 
         Ok(config)
     }
 
-    pub fn get_default_service_config(&self) -> Option<&ServiceConfig> {
-        self.services.get(self.default_service.as_str())
+    /// Whether to offer converting `config.json`, which is asked only once.
+    pub fn should_offer_toml_conversion() -> bool {
+        Self::uses_legacy_json()
+            && Self::get_config_file().exists()
+            && !Self::get_config_dir().join(KEEP_JSON_MARKER).exists()
+    }
+
+    /// Remembers that the user wants to keep `config.json` for now.
+    pub fn decline_toml_conversion() -> Result<(), io::Error> {
+        fs::write(Self::get_config_dir().join(KEEP_JSON_MARKER), "")
+    }
+
+    /// Converts a `config.json` to `config.toml` and moves the JSON file aside.
+    /// Returns the path of the old file.
+    pub fn convert_to_toml() -> Result<PathBuf, io::Error> {
+        if !Self::uses_legacy_json() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("There is no {} to convert", LEGACY_CONFIG_FILE),
+            ));
+        }
+
+        let config = Self::load()?;
+        let config_dir = Self::get_config_dir();
+        let backup = config_dir.join(format!("{}.bak", LEGACY_CONFIG_FILE));
+
+        // Write the TOML file first so a failure leaves the JSON file in place
+        let contents = format!(
+            "{}{}",
+            toml_file::CONFIG_HEADER,
+            toml_file::to_document(&config)?
+        );
+        write_private(&config_dir.join(CONFIG_FILE), &contents)?;
+        // The JSON file may hold plaintext tokens, so the backup gets private
+        // permissions whatever the original had
+        let legacy = config_dir.join(LEGACY_CONFIG_FILE);
+        write_private(&backup, &fs::read_to_string(&legacy)?)?;
+        fs::remove_file(&legacy)?;
+
+        Ok(backup)
+    }
+
+    /// Loads the config file, if there is one, and applies the environment
+    /// overrides described in [`Config::apply_env`].
+    pub fn load_with_env() -> Result<Self, io::Error> {
+        let config = match Self::load() {
+            Ok(config) => config,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(e),
+        };
+
+        config.apply_env(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+    }
+
+    /// Reads the environment variables that override the config file. They
+    /// apply to the profile [`Config::resolve_profile`] picks:
+    ///
+    /// 1. `GCMGEN_SERVICE` selects the profile, unless `--profile` is given.
+    /// 2. `OPENAI_API_KEY` and `ANTHROPIC_API_KEY` provide the token of profiles
+    ///    with that provider.
+    /// 3. `GCMGEN_API_KEY` provides the token of the profile.
+    /// 4. `GCMGEN_MODEL` sets the model of the profile.
+    ///
+    /// Without a profile for a provider whose key is set, one is added, so no
+    /// config file is needed. Tokens are references to the variables, so saving
+    /// the result never writes them to disk.
+    fn apply_env(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self, io::Error> {
+        for (provider, key_var) in PROVIDER_KEY_VARS {
+            if var(key_var).is_none() {
+                continue;
+            }
+            self.env.provider_keys.push((provider, key_var));
+            if !self
+                .services
+                .iter()
+                .any(|(name, service_config)| service_config.provider(name) == Some(provider))
+            {
+                self.service_entry(&provider.to_string()).api_token = ApiToken::Env {
+                    env: key_var.to_string(),
+                };
+            }
+        }
+
+        self.env.service = var("GCMGEN_SERVICE");
+        self.env.api_key = var("GCMGEN_API_KEY").is_some();
+        self.env.model = var("GCMGEN_MODEL");
+
+        if let Some(service) = self.env.service.clone() {
+            if self.env.api_key {
+                self.service_entry(&service);
+            }
+        } else if !self.services.contains_key(&self.default_service) {
+            if let Some((provider, _)) = PROVIDER_KEY_VARS
+                .iter()
+                .find(|(provider, _)| self.services.contains_key(&provider.to_string()))
+            {
+                self.default_service = provider.to_string();
+            }
+        }
+
+        let service = self.env.service.as_ref().unwrap_or(&self.default_service);
+        if service.is_empty() || !self.services.contains_key(service) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No API key configured for service '{}'", service),
+            ));
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the config of `service`, adding one with the default model and no
+    /// token if it doesn't exist yet.
+    fn service_entry(&mut self, service: &str) -> &mut ServiceConfig {
+        self.services
+            .entry(service.to_string())
+            .or_insert_with(|| ServiceConfig {
+                provider: Provider::from_name(service),
+                api_token: ApiToken::Plain(String::new()),
+                model: default_model(service).unwrap_or_default().to_string(),
+            })
+    }
+
+    /// Picks the profile for `task`: the explicitly requested one, else the one
+    /// in `GCMGEN_SERVICE`, else the one assigned to the task, else the default.
+    /// The profile comes with the environment overrides applied.
+    pub fn resolve_profile(
+        &self,
+        requested: Option<&str>,
+        task: Task,
+    ) -> Result<(&str, ServiceConfig, Provider), String> {
+        let name = requested
+            .or(self.env.service.as_deref())
+            .or(self.task_profiles.get(task).map(String::as_str))
+            .unwrap_or(&self.default_service);
+
+        let (name, service_config) = self
+            .services
+            .get_key_value(name)
+            .ok_or_else(|| format!("Profile '{}' not found", name))?;
+        let provider = service_config
+            .provider(name)
+            .ok_or_else(|| format!("Profile '{}' has no provider", name))?;
+        let mut service_config = service_config.clone();
+        self.env.apply(&mut service_config, provider);
+
+        Ok((name, service_config, provider))
+    }
+
+    /// Assigns a profile to a task and saves the config.
+    pub fn set_task_profile(&mut self, task: Task, profile: &str) -> Result<(), String> {
+        if !self.services.contains_key(profile) {
+            return Err(format!("Service '{}' not found", profile));
+        }
+
+        let slot = match task {
+            Task::Commit => &mut self.task_profiles.commit,
+            Task::PullRequest => &mut self.task_profiles.pr,
+        };
+        *slot = Some(profile.to_string());
+        self.save().map_err(|e| e.to_string())
+    }
+
+    /// Returns the value at a dotted key such as `services.OpenAI.model`.
+    pub fn get_value(&self, key: &str) -> Result<Value, String> {
+        let value = serde_json::to_value(self).map_err(|e| e.to_string())?;
+
+        value
+            .pointer(&key_to_pointer(key))
+            .cloned()
+            .ok_or_else(|| format!("Key '{}' is not set", key))
+    }
+
+    /// Sets the value at a dotted key. The value is parsed as JSON when possible
+    /// and used as a string otherwise.
+    pub fn set_value(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = serde_json::from_str(value).unwrap_or(Value::String(value.to_string()));
+        let mut config = serde_json::to_value(&*self).map_err(|e| e.to_string())?;
+
+        let segments: Vec<&str> = key.split('.').collect();
+        let (last, parents) = segments
+            .split_last()
+            .filter(|(last, _)| !last.is_empty())
+            .ok_or_else(|| "Key can't be empty".to_string())?;
+
+        let mut current = &mut config;
+        for segment in parents {
+            current = current
+                .as_object_mut()
+                .ok_or_else(|| format!("Key '{}' is not a table", key))?
+                .entry(segment.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+        current
+            .as_object_mut()
+            .ok_or_else(|| format!("Key '{}' is not a table", key))?
+            .insert(last.to_string(), value);
+
+        let config: Config = serde_json::from_value(config)
+            .map_err(|e| format!("Invalid value for '{}': {}", key, e))?;
+        // Unknown keys are dropped when deserializing
+        config
+            .get_value(key)
+            .map_err(|_| format!("Unknown key '{}'", key))?;
+
+        *self = config;
+        Ok(())
+    }
+
+    /// Removes the value at a dotted key.
+    pub fn unset_value(&mut self, key: &str) -> Result<(), String> {
+        let mut config = serde_json::to_value(&*self).map_err(|e| e.to_string())?;
+
+        let (parent, last) = match key.rsplit_once('.') {
+            Some((parent, last)) => (key_to_pointer(parent), last),
+            None => (String::new(), key),
+        };
+        config
+            .pointer_mut(&parent)
+            .and_then(Value::as_object_mut)
+            .and_then(|object| object.remove(last))
+            .ok_or_else(|| format!("Key '{}' is not set", key))?;
+
+        *self =
+            serde_json::from_value(config).map_err(|e| format!("Can't unset '{}': {}", key, e))?;
+        Ok(())
+    }
+
+    /// Removes a profile, and clears the default and task profiles that used it.
+    pub fn remove_service(&mut self, service_name: &str) -> Result<(), String> {
+        if self.services.remove(service_name).is_none() {
+            return Err(format!("Service '{}' not found", service_name));
+        }
+
+        if self.default_service == service_name {
+            let mut remaining: Vec<&String> = self.services.keys().collect();
+            remaining.sort();
+            self.default_service = remaining
+                .first()
+                .map(|name| name.to_string())
+                .unwrap_or_default();
+        }
+        if self.task_profiles.commit.as_deref() == Some(service_name) {
+            self.task_profiles.commit = None;
+        }
+        if self.task_profiles.pr.as_deref() == Some(service_name) {
+            self.task_profiles.pr = None;
+        }
+
+        Ok(())
     }
 
     pub fn set_default_service(&mut self, service_name: &str) -> Result<(), String> {
         if self.services.contains_key(service_name) {
             self.default_service = service_name.to_string();
-            self.save().unwrap();
-            Ok(())
+            self.save().map_err(|e| e.to_string())
         } else {
             Err(format!("Service '{}' not found", service_name))
         }
@@ -121,7 +744,7 @@ mod tests {
     fn test_save_creates_config_file() {
         let (xdg_config_home, _) = setup_temp_env();
         let config_dir = xdg_config_home.join(CONFIG_DIRECTORY);
-        let config_file = config_dir.join("config.json");
+        let config_file = config_dir.join(CONFIG_FILE);
 
         // Ensure the directory exists before proceeding
         fs::create_dir_all(&config_dir).unwrap();
@@ -132,7 +755,8 @@ mod tests {
         }
 
         let service_config = ServiceConfig {
-            api_token: "test_token".to_string(),
+            provider: None,
+            api_token: ApiToken::Plain("test_token".to_string()),
             model: "gpt-4o-mini".to_string(),
         };
 
@@ -142,6 +766,7 @@ mod tests {
                 .iter()
                 .cloned()
                 .collect(),
+            ..Default::default()
         };
 
         config.save().unwrap();
@@ -158,13 +783,14 @@ mod tests {
     fn test_load_reads_correct_config() {
         let (xdg_config_home, _) = setup_temp_env();
         let config_dir = xdg_config_home.join(CONFIG_DIRECTORY);
-        let config_file = config_dir.join("config.json");
+        let config_file = config_dir.join(CONFIG_FILE);
 
         // Ensure the directory exists before proceeding
         fs::create_dir_all(&config_dir).unwrap();
 
         let service_config = ServiceConfig {
-            api_token: "test_token".to_string(),
+            provider: None,
+            api_token: ApiToken::Plain("test_token".to_string()),
             model: "gpt-4o-mini".to_string(),
         };
 
@@ -174,6 +800,7 @@ mod tests {
                 .iter()
                 .cloned()
                 .collect(),
+            ..Default::default()
         };
 
         config.save().unwrap();
@@ -195,7 +822,7 @@ mod tests {
     fn test_load_returns_error_when_file_missing() {
         let (xdg_config_home, _) = setup_temp_env();
         let config_dir = xdg_config_home.join(CONFIG_DIRECTORY);
-        let config_file = config_dir.join("config.json");
+        let config_file = config_dir.join(CONFIG_FILE);
 
         // Ensure the directory exists before proceeding
         fs::create_dir_all(&config_dir).unwrap();
@@ -215,15 +842,17 @@ mod tests {
     fn test_set_default_service() {
         let (xdg_config_home, _) = setup_temp_env();
         let config_dir = xdg_config_home.join(CONFIG_DIRECTORY);
-        let config_file = config_dir.join("config.json");
+        let config_file = config_dir.join(CONFIG_FILE);
 
         let service_config_openai = ServiceConfig {
-            api_token: "test_token_openai".to_string(),
+            provider: None,
+            api_token: ApiToken::Plain("test_token_openai".to_string()),
             model: "gpt-4o-mini".to_string(),
         };
 
         let service_config_anthropic = ServiceConfig {
-            api_token: "test_token_anthropic".to_string(),
+            provider: None,
+            api_token: ApiToken::Plain("test_token_anthropic".to_string()),
             model: "claude-v1".to_string(),
         };
 
@@ -236,6 +865,7 @@ mod tests {
             .iter()
             .cloned()
             .collect(),
+            ..Default::default()
         };
 
         config.save().unwrap();
@@ -251,6 +881,525 @@ mod tests {
         fs::remove_file(config_file).unwrap();
     }
 
+    #[test]
+    #[serial]
+    fn test_load_reads_config_without_forges() {
+        let (xdg_config_home, _) = setup_temp_env();
+        let config_dir = xdg_config_home.join(CONFIG_DIRECTORY);
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.json"),
+            r#"{"default_service":"OpenAI","services":{}}"#,
+        )
+        .unwrap();
+
+        let loaded_config = Config::load().unwrap();
+        assert!(loaded_config.forges.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(unix)]
+    fn test_save_restricts_config_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (xdg_config_home, _) = setup_temp_env();
+        let config_dir = xdg_config_home.join(CONFIG_DIRECTORY);
+        let config_file = config_dir.join("config.json");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(&config_file, "{}").unwrap();
+        fs::set_permissions(&config_file, fs::Permissions::from_mode(0o644)).unwrap();
+
+        Config::default().save().unwrap();
+
+        let mode = fs::metadata(&config_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_api_token_deserializes_every_source() {
+        let services: HashMap<String, ServiceConfig> = serde_json::from_str(
+            r#"{
+                "plain": {"api_token": "sk-123", "model": "m"},
+                "keyring": {"api_token": {"keyring": "OpenAI"}, "model": "m"},
+                "command": {"api_token": {"command": "pass show openai"}, "model": "m"},
+                "env": {"api_token": {"env": "OPENAI_API_KEY"}, "model": "m"}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            services["plain"].api_token,
+            ApiToken::Plain("sk-123".to_string())
+        );
+        assert_eq!(
+            services["keyring"].api_token,
+            ApiToken::Keyring {
+                keyring: "OpenAI".to_string()
+            }
+        );
+        assert_eq!(
+            services["command"].api_token,
+            ApiToken::Command {
+                command: "pass show openai".to_string()
+            }
+        );
+        assert_eq!(
+            services["env"].api_token,
+            ApiToken::Env {
+                env: "OPENAI_API_KEY".to_string()
+            }
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_api_token_resolves_env_and_command() {
+        env::set_var("GCMGEN_TEST_TOKEN", "env-token");
+        let token = ApiToken::Env {
+            env: "GCMGEN_TEST_TOKEN".to_string(),
+        };
+        assert_eq!(token.resolve().unwrap(), "env-token");
+        env::remove_var("GCMGEN_TEST_TOKEN");
+        assert!(token.resolve().is_err());
+
+        let token = ApiToken::Command {
+            command: "printf 'cmd-token\\nlogin: me'".to_string(),
+        };
+        assert_eq!(token.resolve().unwrap(), "cmd-token");
+
+        let token = ApiToken::Command {
+            command: "exit 1".to_string(),
+        };
+        assert!(token.resolve().is_err());
+    }
+
+    fn env_from(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_apply_env_builds_config_without_file() {
+        let config = Config::default()
+            .apply_env(env_from(&[("ANTHROPIC_API_KEY", "sk-ant")]))
+            .unwrap();
+
+        assert_eq!(config.default_service, "Anthropic");
+        assert_eq!(
+            &config.services[&config.default_service],
+            &ServiceConfig {
+                provider: Some(Provider::Anthropic),
+                api_token: ApiToken::Env {
+                    env: "ANTHROPIC_API_KEY".to_string()
+                },
+                model: "claude-3-5-sonnet-20240620".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_env_overrides_config_file() {
+        let config = Config {
+            default_service: "OpenAI".to_string(),
+            services: [(
+                "OpenAI".to_string(),
+                ServiceConfig {
+                    provider: None,
+                    api_token: ApiToken::Plain("file_token".to_string()),
+                    model: "gpt-4o-mini".to_string(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+
+        let config = config
+            .apply_env(env_from(&[
+                ("GCMGEN_SERVICE", "Anthropic"),
+                ("GCMGEN_MODEL", "claude-3-opus"),
+                ("GCMGEN_API_KEY", "sk-ant"),
+            ]))
+            .unwrap();
+
+        let (name, service_config, provider) = config.resolve_profile(None, Task::Commit).unwrap();
+        assert_eq!((name, provider), ("Anthropic", Provider::Anthropic));
+        assert_eq!(service_config.model, "claude-3-opus");
+        assert_eq!(
+            service_config.api_token,
+            ApiToken::Env {
+                env: "GCMGEN_API_KEY".to_string()
+            }
+        );
+        assert_eq!(
+            config.services["OpenAI"].api_token,
+            ApiToken::Plain("file_token".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_env_fails_without_any_token() {
+        let result = Config::default().apply_env(env_from(&[("GCMGEN_MODEL", "gpt-4o")]));
+        assert!(result.is_err());
+
+        let result = Config::default().apply_env(env_from(&[("GCMGEN_SERVICE", "OpenAI")]));
+        assert!(result.is_err());
+    }
+
+    fn profile(provider: Option<Provider>, model: &str) -> ServiceConfig {
+        ServiceConfig {
+            provider,
+            api_token: ApiToken::Plain("token".to_string()),
+            model: model.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_profile_prefers_requested_then_task_then_default() {
+        let config = Config {
+            default_service: "OpenAI".to_string(),
+            services: [
+                ("OpenAI".to_string(), profile(None, "gpt-4o-mini")),
+                (
+                    "work".to_string(),
+                    profile(Some(Provider::OpenAI), "gpt-4o"),
+                ),
+                (
+                    "strong".to_string(),
+                    profile(Some(Provider::Anthropic), "claude-3-opus"),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            task_profiles: TaskProfiles {
+                commit: None,
+                pr: Some("strong".to_string()),
+            },
+            ..Default::default()
+        };
+
+        let (name, _, provider) = config.resolve_profile(None, Task::Commit).unwrap();
+        assert_eq!((name, provider), ("OpenAI", Provider::OpenAI));
+
+        let (name, _, provider) = config.resolve_profile(None, Task::PullRequest).unwrap();
+        assert_eq!((name, provider), ("strong", Provider::Anthropic));
+
+        let (name, service_config, provider) = config
+            .resolve_profile(Some("work"), Task::PullRequest)
+            .unwrap();
+        assert_eq!((name, provider), ("work", Provider::OpenAI));
+        assert_eq!(service_config.model, "gpt-4o");
+
+        assert!(config
+            .resolve_profile(Some("missing"), Task::Commit)
+            .is_err());
+    }
+
+    #[test]
+    fn test_resolve_profile_applies_env_after_task_profile() {
+        let config = Config {
+            default_service: "OpenAI".to_string(),
+            services: [
+                ("OpenAI".to_string(), profile(None, "gpt-4o-mini")),
+                (
+                    "work".to_string(),
+                    profile(Some(Provider::OpenAI), "gpt-4o"),
+                ),
+                (
+                    "strong".to_string(),
+                    profile(Some(Provider::Anthropic), "claude-3-opus"),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            task_profiles: TaskProfiles {
+                commit: None,
+                pr: Some("strong".to_string()),
+            },
+            ..Default::default()
+        }
+        .apply_env(env_from(&[
+            ("GCMGEN_SERVICE", "work"),
+            ("GCMGEN_API_KEY", "sk-env"),
+            ("GCMGEN_MODEL", "gpt-4.1"),
+        ]))
+        .unwrap();
+
+        let (name, service_config, _) = config.resolve_profile(None, Task::PullRequest).unwrap();
+        assert_eq!(name, "work");
+        assert_eq!(service_config.model, "gpt-4.1");
+        assert_eq!(
+            service_config.api_token,
+            ApiToken::Env {
+                env: "GCMGEN_API_KEY".to_string()
+            }
+        );
+
+        let (name, service_config, _) = config
+            .resolve_profile(Some("strong"), Task::Commit)
+            .unwrap();
+        assert_eq!(name, "strong");
+        assert_eq!(service_config.model, "gpt-4.1");
+        assert_eq!(config.services["strong"].model, "claude-3-opus");
+    }
+
+    #[test]
+    fn test_resolve_profile_applies_provider_keys_by_provider() {
+        let config = Config {
+            default_service: "work".to_string(),
+            services: [
+                (
+                    "work".to_string(),
+                    profile(Some(Provider::OpenAI), "gpt-4o"),
+                ),
+                (
+                    "strong".to_string(),
+                    profile(Some(Provider::Anthropic), "claude-3-opus"),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        }
+        .apply_env(env_from(&[("OPENAI_API_KEY", "sk-openai")]))
+        .unwrap();
+
+        assert!(!config.services.contains_key("OpenAI"));
+        let (name, service_config, _) = config.resolve_profile(None, Task::Commit).unwrap();
+        assert_eq!(name, "work");
+        assert_eq!(
+            service_config.api_token,
+            ApiToken::Env {
+                env: "OPENAI_API_KEY".to_string()
+            }
+        );
+
+        let (_, service_config, _) = config
+            .resolve_profile(Some("strong"), Task::Commit)
+            .unwrap();
+        assert_eq!(
+            service_config.api_token,
+            ApiToken::Plain("token".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_profile_requires_provider_for_custom_names() {
+        let config = Config {
+            default_service: "personal".to_string(),
+            services: [("personal".to_string(), profile(None, "gpt-4o"))]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+
+        assert!(config.resolve_profile(None, Task::Commit).is_err());
+    }
+
+    fn two_profile_config() -> Config {
+        Config {
+            default_service: "OpenAI".to_string(),
+            services: [
+                ("OpenAI".to_string(), profile(None, "gpt-4o-mini")),
+                (
+                    "work".to_string(),
+                    profile(Some(Provider::OpenAI), "gpt-4o"),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            task_profiles: TaskProfiles {
+                commit: Some("OpenAI".to_string()),
+                pr: Some("work".to_string()),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_value_reads_dotted_keys() {
+        let config = two_profile_config();
+
+        assert_eq!(config.get_value("default_service").unwrap(), "OpenAI");
+        assert_eq!(config.get_value("services.work.model").unwrap(), "gpt-4o");
+        assert!(config.get_value("services.missing.model").is_err());
+    }
+
+    #[test]
+    fn test_mask_tokens_masks_tokens_under_parent_keys() {
+        let mut config = two_profile_config();
+        config.services.get_mut("work").unwrap().api_token =
+            ApiToken::Plain("sk-secret-work-token".to_string());
+
+        let mut services = config.get_value("services").unwrap();
+        mask_tokens(&mut services);
+
+        assert_eq!(services["work"]["api_token"], "sk-se***");
+        assert_eq!(services["work"]["model"], "gpt-4o");
+        assert!(!services.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn test_set_value_creates_nested_tables_and_validates() {
+        let mut config = two_profile_config();
+
+        config.set_value("services.work.model", "gpt-4.1").unwrap();
+        config.set_value("settings.base_branch", "develop").unwrap();
+        config
+            .set_value("settings.ignore", r#"["*.lock"]"#)
+            .unwrap();
+
+        assert_eq!(config.services["work"].model, "gpt-4.1");
+        assert_eq!(config.settings.base_branch(), "develop");
+        assert_eq!(config.settings.ignore, vec!["*.lock"]);
+
+        assert!(config
+            .set_value("services.work.provider", "Mistral")
+            .is_err());
+        assert!(config.set_value("unknown_key.nested", "value").is_err());
+        assert_eq!(config.services["work"].provider, Some(Provider::OpenAI));
+    }
+
+    #[test]
+    fn test_unset_value_removes_optional_keys() {
+        let mut config = two_profile_config();
+
+        config.unset_value("task_profiles.pr").unwrap();
+        assert_eq!(config.task_profiles.pr, None);
+
+        assert!(config.unset_value("task_profiles.pr").is_err());
+        assert!(config.unset_value("default_service").is_err());
+    }
+
+    #[test]
+    fn test_remove_service_clears_references() {
+        let mut config = two_profile_config();
+
+        config.remove_service("OpenAI").unwrap();
+
+        assert_eq!(config.default_service, "work");
+        assert_eq!(config.task_profiles.commit, None);
+        assert_eq!(config.task_profiles.pr, Some("work".to_string()));
+        assert!(config.remove_service("OpenAI").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_migrates_old_config_in_place_with_backup() {
+        let (xdg_config_home, _) = setup_temp_env();
+        let config_dir = xdg_config_home.join(CONFIG_DIRECTORY);
+        fs::create_dir_all(&config_dir).unwrap();
+        let old_config = include_str!("../tests/fixtures/config/v0.json");
+        fs::write(config_dir.join("config.json"), old_config).unwrap();
+
+        let loaded_config = Config::load().unwrap();
+        assert_eq!(loaded_config.version, migrate::CURRENT_VERSION);
+
+        let backup = fs::read_to_string(config_dir.join("config.v0.json.bak")).unwrap();
+        assert_eq!(backup, old_config);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(config_dir.join("config.v0.json.bak"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let saved: Value =
+            serde_json::from_str(&fs::read_to_string(config_dir.join("config.json")).unwrap())
+                .unwrap();
+        assert_eq!(saved["version"], migrate::CURRENT_VERSION);
+    }
+
+    #[test]
+    #[serial]
+    fn test_save_keeps_comments_in_toml_file() {
+        let (xdg_config_home, _) = setup_temp_env();
+        let config_dir = xdg_config_home.join(CONFIG_DIRECTORY);
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join(CONFIG_FILE),
+            r#"version = 1
+# Used when no profile is given
+default_service = "OpenAI"
+
+[services.OpenAI]
+provider = "OpenAI"
+api_token = { env = "OPENAI_API_KEY" } # exported in ~/.profile
+model = "gpt-4o-mini"
+"#,
+        )
+        .unwrap();
+
+        let mut config = Config::load().unwrap();
+        assert_eq!(
+            config.services["OpenAI"].api_token,
+            ApiToken::Env {
+                env: "OPENAI_API_KEY".to_string()
+            }
+        );
+
+        config.services.get_mut("OpenAI").unwrap().model = "gpt-4o".to_string();
+        config.save().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(config_dir.join(CONFIG_FILE)).unwrap(),
+            r#"version = 1
+# Used when no profile is given
+default_service = "OpenAI"
+
+[services.OpenAI]
+provider = "OpenAI"
+api_token = { env = "OPENAI_API_KEY" } # exported in ~/.profile
+model = "gpt-4o"
+"#
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_convert_to_toml_keeps_settings() {
+        let (xdg_config_home, _) = setup_temp_env();
+        let config_dir = xdg_config_home.join(CONFIG_DIRECTORY);
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join(LEGACY_CONFIG_FILE),
+            include_str!("../tests/fixtures/config/v0_extended.json"),
+        )
+        .unwrap();
+        assert!(Config::uses_legacy_json());
+
+        let backup = Config::convert_to_toml().unwrap();
+
+        assert!(!Config::uses_legacy_json());
+        assert!(backup.exists());
+        assert!(!config_dir.join(LEGACY_CONFIG_FILE).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&backup).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let config = Config::load().unwrap();
+        assert_eq!(config.default_service, "work");
+        assert_eq!(config.services["work"].model, "gpt-4o");
+        assert_eq!(
+            config.services["work"].api_token,
+            ApiToken::Keyring {
+                keyring: "work".to_string()
+            }
+        );
+        assert_eq!(config.settings.base_branch(), "develop");
+        assert!(Config::convert_to_toml().is_err());
+    }
+
     #[test]
     #[serial]
     fn test_get_config_dir_respects_xdg_config_home() {
@@ -0,0 +1,372 @@
+use crate::config::ForgeConfig;
+use crate::forge::{PullRequestOptions, PullRequestTarget};
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+use std::error::Error;
+
+/// Items per page when listing labels or milestones, the default maximum of
+/// Forgejo.
+const PAGE_SIZE: usize = 50;
+
+/// Creates pull requests through the Gitea/Forgejo REST API.
+pub struct ForgejoClient {
+    api_token: String,
+    base_url: String,
+    client: Client,
+}
+
+impl ForgejoClient {
+    pub fn new(forge_config: &ForgeConfig) -> Result<Self, std::io::Error> {
+        Ok(ForgejoClient {
+            api_token: forge_config.api_token.resolve()?,
+            base_url: forge_config.base_url.trim_end_matches('/').to_string(),
+            client: Client::new(),
+        })
+    }
+
+    fn repo_url(&self, owner: &str, repo: &str) -> String {
+        format!("{}/api/v1/repos/{}/{}", self.base_url, owner, repo)
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: Option<Value>,
+    ) -> Result<Value, Box<dyn Error>> {
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("Authorization", format!("token {}", self.api_token));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request.send()?;
+        let status = response.status();
+        let response_json: Value = response.json().unwrap_or(Value::Null);
+
+        if status.is_success() {
+            Ok(response_json)
+        } else {
+            Err(Box::new(std::io::Error::other(format!(
+                "Forgejo API request to {} failed with {}: {}",
+                url, status, response_json
+            ))))
+        }
+    }
+
+    /// Looks up the ids of items in a list endpoint (labels, milestones) by name.
+    /// Pages are fetched until every name is found or a page comes back empty.
+    fn find_ids(
+        &self,
+        url: &str,
+        name_field: &str,
+        names: &[String],
+    ) -> Result<Vec<u64>, Box<dyn Error>> {
+        let mut ids: Vec<Option<u64>> = vec![None; names.len()];
+
+        let mut page = 1;
+        while ids.iter().any(Option::is_none) {
+            let items = self.request(
+                reqwest::Method::GET,
+                &format!("{}?page={}&limit={}", url, page, PAGE_SIZE),
+                None,
+            )?;
+            let items = items.as_array().cloned().unwrap_or_default();
+            if items.is_empty() {
+                break;
+            }
+
+            for (name, id) in names.iter().zip(ids.iter_mut()) {
+                if id.is_none() {
+                    *id = items
+                        .iter()
+                        .find(|item| item.get(name_field).and_then(Value::as_str) == Some(name))
+                        .and_then(|item| item.get("id"))
+                        .and_then(Value::as_u64);
+                }
+            }
+            page += 1;
+        }
+
+        names
+            .iter()
+            .zip(ids)
+            .map(|(name, id)| {
+                id.ok_or_else(|| {
+                    Box::new(std::io::Error::other(format!("'{}' not found", name)))
+                        as Box<dyn Error>
+                })
+            })
+            .collect()
+    }
+
+    /// Creates a pull request and returns its URL.
+    pub fn create_pull_request(
+        &self,
+        target: &PullRequestTarget,
+        title: &str,
+        description: &str,
+        options: &PullRequestOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        let repo_url = self.repo_url(&target.owner, &target.repo);
+
+        // Forgejo marks pull requests as drafts by their title prefix
+        let title = if options.draft {
+            format!("WIP: {}", title)
+        } else {
+            title.to_string()
+        };
+
+        let mut body = json!({
+            "title": title,
+            "body": description,
+            "head": target.head,
+            "base": target.base,
+            "assignees": options.assignees,
+        });
+        if !options.labels.is_empty() {
+            let labels = self.find_ids(&format!("{}/labels", repo_url), "name", &options.labels)?;
+            body["labels"] = json!(labels);
+        }
+        if let Some(milestone) = &options.milestone {
+            let ids = self.find_ids(
+                &format!("{}/milestones", repo_url),
+                "title",
+                std::slice::from_ref(milestone),
+            )?;
+            body["milestone"] = json!(ids[0]);
+        }
+
+        let pull_request = self.request(
+            reqwest::Method::POST,
+            &format!("{}/pulls", repo_url),
+            Some(body),
+        )?;
+
+        if !options.reviewers.is_empty() {
+            let number = pull_request
+                .get("number")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| {
+                    Box::new(std::io::Error::other(format!(
+                        "Failed to request reviewers. The response has no pull request number: {}",
+                        pull_request
+                    ))) as Box<dyn Error>
+                })?;
+            self.request(
+                reqwest::Method::POST,
+                &format!("{}/pulls/{}/requested_reviewers", repo_url, number),
+                Some(json!({ "reviewers": options.reviewers })),
+            )?;
+        }
+
+        pull_request
+            .get("html_url")
+            .and_then(Value::as_str)
+            .map(|url| url.to_string())
+            .ok_or_else(|| {
+                Box::new(std::io::Error::other(format!(
+                    "Failed to create pull request. Unexpected response format: {}",
+                    pull_request
+                ))) as Box<dyn Error>
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiToken, ForgeKind};
+    use crate::test_server::StubServer;
+
+    fn target() -> PullRequestTarget {
+        PullRequestTarget {
+            owner: "owner".to_string(),
+            repo: "repo".to_string(),
+            head: "feature".to_string(),
+            base: "main".to_string(),
+        }
+    }
+
+    fn client_for(server: &StubServer) -> ForgejoClient {
+        ForgejoClient::new(&ForgeConfig {
+            kind: ForgeKind::Forgejo,
+            base_url: format!("{}/", server.base_url),
+            api_token: ApiToken::Plain("secret".to_string()),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_create_pull_request_posts_pull_and_returns_url() {
+        let server = StubServer::start(vec![(
+            "POST /api/v1/repos/owner/repo/pulls",
+            201,
+            r#"{"number": 7, "html_url": "https://forge.example/owner/repo/pulls/7"}"#,
+        )]);
+
+        let url = client_for(&server)
+            .create_pull_request(
+                &target(),
+                "Add feature",
+                "Description",
+                &PullRequestOptions::default(),
+            )
+            .unwrap();
+
+        assert_eq!(url, "https://forge.example/owner/repo/pulls/7");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].headers["authorization"], "token secret");
+        let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body["title"], "Add feature");
+        assert_eq!(body["head"], "feature");
+        assert_eq!(body["base"], "main");
+    }
+
+    #[test]
+    fn test_create_pull_request_resolves_labels_and_requests_reviewers() {
+        let server = StubServer::start(vec![
+            (
+                "GET /api/v1/repos/owner/repo/labels?page=1&limit=50",
+                200,
+                r#"[{"id": 1, "name": "bug"}, {"id": 2, "name": "backend"}]"#,
+            ),
+            (
+                "POST /api/v1/repos/owner/repo/pulls",
+                201,
+                r#"{"number": 7, "html_url": "https://forge.example/owner/repo/pulls/7"}"#,
+            ),
+            (
+                "POST /api/v1/repos/owner/repo/pulls/7/requested_reviewers",
+                201,
+                "[]",
+            ),
+        ]);
+        let options = PullRequestOptions {
+            draft: true,
+            labels: vec!["backend".to_string()],
+            reviewers: vec!["alice".to_string()],
+            ..Default::default()
+        };
+
+        client_for(&server)
+            .create_pull_request(&target(), "Add feature", "", &options)
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["title"], "WIP: Add feature");
+        assert_eq!(body["labels"], json!([2]));
+        let body: Value = serde_json::from_str(&requests[2].body).unwrap();
+        assert_eq!(body["reviewers"], json!(["alice"]));
+    }
+
+    #[test]
+    fn test_create_pull_request_follows_label_pages() {
+        let server = StubServer::start(vec![
+            (
+                "GET /api/v1/repos/owner/repo/labels?page=1&limit=50",
+                200,
+                r#"[{"id": 1, "name": "bug"}]"#,
+            ),
+            (
+                "GET /api/v1/repos/owner/repo/labels?page=2&limit=50",
+                200,
+                r#"[{"id": 2, "name": "backend"}]"#,
+            ),
+            (
+                "POST /api/v1/repos/owner/repo/pulls",
+                201,
+                r#"{"number": 7, "html_url": "https://forge.example/owner/repo/pulls/7"}"#,
+            ),
+        ]);
+        let options = PullRequestOptions {
+            labels: vec!["backend".to_string(), "bug".to_string()],
+            ..Default::default()
+        };
+
+        client_for(&server)
+            .create_pull_request(&target(), "Add feature", "", &options)
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let body: Value = serde_json::from_str(&requests[2].body).unwrap();
+        assert_eq!(body["labels"], json!([2, 1]));
+    }
+
+    #[test]
+    fn test_create_pull_request_fails_for_unknown_labels() {
+        let server = StubServer::start(vec![
+            (
+                "GET /api/v1/repos/owner/repo/labels?page=1&limit=50",
+                200,
+                r#"[{"id": 1, "name": "bug"}]"#,
+            ),
+            (
+                "GET /api/v1/repos/owner/repo/labels?page=2&limit=50",
+                200,
+                "[]",
+            ),
+        ]);
+        let options = PullRequestOptions {
+            labels: vec!["backend".to_string()],
+            ..Default::default()
+        };
+
+        let error = client_for(&server)
+            .create_pull_request(&target(), "Add feature", "", &options)
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "'backend' not found");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_create_pull_request_returns_error_on_failure() {
+        let server = StubServer::start(vec![(
+            "POST /api/v1/repos/owner/repo/pulls",
+            409,
+            r#"{"message": "pull request already exists"}"#,
+        )]);
+
+        let result = client_for(&server).create_pull_request(
+            &target(),
+            "Add feature",
+            "",
+            &PullRequestOptions::default(),
+        );
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("pull request already exists"));
+    }
+
+    #[test]
+    fn test_create_pull_request_requires_number_for_reviewers() {
+        let server = StubServer::start(vec![(
+            "POST /api/v1/repos/owner/repo/pulls",
+            201,
+            r#"{"html_url": "https://forge.example/owner/repo/pulls/7"}"#,
+        )]);
+        let options = PullRequestOptions {
+            reviewers: vec!["alice".to_string()],
+            ..Default::default()
+        };
+
+        let error = client_for(&server)
+            .create_pull_request(&target(), "Add feature", "", &options)
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains("no pull request number"));
+        assert!(error.contains("https://forge.example/owner/repo/pulls/7"));
+        assert_eq!(server.requests().len(), 1);
+    }
+}
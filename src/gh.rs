@@ -1,17 +1,26 @@
+use crate::forge::PullRequestOptions;
+use serde::Deserialize;
 use std::error::Error;
 use std::process::Command;
 
-#[allow(dead_code)]
-pub fn create_pull_request(
+/// The fields of an existing pull request that gcmgen reads from `gh pr view`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    pub body: String,
+    pub base_ref_name: String,
+    pub url: String,
+}
+
+fn build_create_args(
     title: &str,
     description: &str,
-    base_branch: Option<&str>,
-) -> Result<(), Box<dyn Error>> {
-    let branch = base_branch.unwrap_or("main"); // main is default
-
-    let mut command = Command::new("gh");
-
-    command.args([
+    branch: &str,
+    options: &PullRequestOptions,
+) -> Vec<String> {
+    let mut args: Vec<String> = [
         "pr",
         "create",
         "--title",
@@ -20,18 +29,323 @@ pub fn create_pull_request(
         description,
         "--base",
         branch,
-        "--web",
-    ]);
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+
+    if options.draft {
+        args.push("--draft".to_string());
+    }
+    for reviewer in &options.reviewers {
+        args.extend(["--reviewer".to_string(), reviewer.clone()]);
+    }
+    for label in &options.labels {
+        args.extend(["--label".to_string(), label.clone()]);
+    }
+    for assignee in &options.assignees {
+        args.extend(["--assignee".to_string(), assignee.clone()]);
+    }
+    if let Some(milestone) = &options.milestone {
+        args.extend(["--milestone".to_string(), milestone.clone()]);
+    }
+    if options.opens_browser() {
+        args.push("--web".to_string());
+    }
+
+    args
+}
+
+/// Creates a pull request with `gh`. Returns the URL of the created pull request,
+/// or `None` when the creation page was opened in the browser.
+pub fn create_pull_request(
+    title: &str,
+    description: &str,
+    base_branch: Option<&str>,
+    options: &PullRequestOptions,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let branch = base_branch.unwrap_or("main"); // main is default
+
+    let mut command = Command::new("gh");
+    command.args(build_create_args(title, description, branch, options));
+
+    if options.opens_browser() {
+        let status = command.status()?;
+
+        return if status.success() {
+            println!("Pull request creation page opened in your browser.");
+            Ok(None)
+        } else {
+            Err(Box::new(std::io::Error::other(
+                "Failed to create pull request",
+            )))
+        };
+    }
+
+    let output = command.output()?;
+
+    if output.status.success() {
+        // gh prints the URL of the new pull request as the last line
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines().last().map(|url| url.trim().to_string()))
+    } else {
+        Err(Box::new(std::io::Error::other(format!(
+            "Failed to create pull request: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))))
+    }
+}
+
+/// Finds a pull request by number, or the open one for the current branch.
+pub fn view_pull_request(number: Option<&str>) -> Result<PullRequest, Box<dyn Error>> {
+    let output = Command::new("gh")
+        .args(["pr", "view"])
+        .args(number)
+        .args(["--json", "number,title,body,baseRefName,url"])
+        .output()?;
+
+    if output.status.success() {
+        Ok(serde_json::from_slice(&output.stdout)?)
+    } else {
+        Err(Box::new(std::io::Error::other(format!(
+            "Failed to find a pull request for the current branch: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))))
+    }
+}
+
+pub fn edit_pull_request(
+    number: u64,
+    title: &str,
+    description: &str,
+) -> Result<(), Box<dyn Error>> {
+    let status = Command::new("gh")
+        .args([
+            "pr",
+            "edit",
+            &number.to_string(),
+            "--title",
+            title,
+            "--body",
+            description,
+        ])
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Box::new(std::io::Error::other(
+            "Failed to update pull request",
+        )))
+    }
+}
+
+/// Returns the diff of a pull request against its base.
+pub fn pull_request_diff(number: u64) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("gh")
+        .args(["pr", "diff", &number.to_string()])
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(Box::new(std::io::Error::other(format!(
+            "Failed to get the diff of pull request #{}: {}",
+            number,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Commit {
+    message_headline: String,
+    message_body: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Commits {
+    commits: Vec<Commit>,
+}
+
+/// Lists the commit messages of a pull request, oldest first, in the same
+/// form as `git::get_branch_commits`.
+pub fn pull_request_commits(number: u64) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("gh")
+        .args(["pr", "view", &number.to_string(), "--json", "commits"])
+        .output()?;
+
+    if output.status.success() {
+        Ok(format_commits(&serde_json::from_slice(&output.stdout)?))
+    } else {
+        Err(Box::new(std::io::Error::other(format!(
+            "Failed to get the commits of pull request #{}: {}",
+            number,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))))
+    }
+}
+
+fn format_commits(commits: &Commits) -> String {
+    commits
+        .commits
+        .iter()
+        .map(|commit| {
+            format!(
+                "{}\n\n{}\n---",
+                commit.message_headline, commit.message_body
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Debug, Deserialize)]
+struct MergeCommit {
+    oid: String,
+}
+
+/// A merged pull request, as `gh pr list --state merged` lists it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergedPullRequest {
+    pub number: u64,
+    pub title: String,
+    merge_commit: Option<MergeCommit>,
+}
+
+impl MergedPullRequest {
+    /// The commit the pull request was merged with, a merge or a squashed commit.
+    pub fn merge_commit(&self) -> Option<&str> {
+        self.merge_commit.as_ref().map(|commit| commit.oid.as_str())
+    }
+}
+
+/// Lists the most recently merged pull requests.
+pub fn merged_pull_requests(limit: u32) -> Result<Vec<MergedPullRequest>, Box<dyn Error>> {
+    let output = Command::new("gh")
+        .args([
+            "pr",
+            "list",
+            "--state",
+            "merged",
+            "--limit",
+            &limit.to_string(),
+            "--json",
+            "number,title,mergeCommit",
+        ])
+        .output()?;
+
+    if output.status.success() {
+        Ok(serde_json::from_slice(&output.stdout)?)
+    } else {
+        Err(Box::new(std::io::Error::other(format!(
+            "Failed to list merged pull requests: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))))
+    }
+}
 
-    let status = command.status()?;
+/// Squash-merges a pull request with the given commit message.
+pub fn merge_pull_request(number: u64, subject: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    let status = Command::new("gh")
+        .args([
+            "pr",
+            "merge",
+            &number.to_string(),
+            "--squash",
+            "--subject",
+            subject,
+            "--body",
+            body,
+        ])
+        .status()?;
 
     if status.success() {
-        println!("Pull request creation page opened in your browser.");
         Ok(())
     } else {
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Failed to create pull request",
+        Err(Box::new(std::io::Error::other(
+            "Failed to merge pull request",
         )))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_create_args_adds_web_when_requested() {
+        let options = PullRequestOptions {
+            web: true,
+            ..Default::default()
+        };
+
+        let args = build_create_args("Title", "Body", "main", &options);
+        assert_eq!(
+            args,
+            vec!["pr", "create", "--title", "Title", "--body", "Body", "--base", "main", "--web"]
+        );
+    }
+
+    #[test]
+    fn test_build_create_args_passes_metadata() {
+        let options = PullRequestOptions {
+            web: false,
+            draft: true,
+            reviewers: vec!["alice".to_string(), "bob".to_string()],
+            labels: vec!["bug".to_string()],
+            assignees: vec!["@me".to_string()],
+            milestone: Some("v2".to_string()),
+        };
+
+        let args = build_create_args("Title", "Body", "develop", &options);
+        assert_eq!(
+            args[8..],
+            [
+                "--draft",
+                "--reviewer",
+                "alice",
+                "--reviewer",
+                "bob",
+                "--label",
+                "bug",
+                "--assignee",
+                "@me",
+                "--milestone",
+                "v2"
+            ]
+        );
+        assert!(!args.contains(&"--web".to_string()));
+    }
+
+    #[test]
+    fn test_build_create_args_creates_drafts_directly() {
+        let options = PullRequestOptions {
+            web: true,
+            draft: true,
+            ..Default::default()
+        };
+
+        let args = build_create_args("Title", "Body", "main", &options);
+        assert_eq!(args[8..], ["--draft"]);
+        assert!(!options.opens_browser());
+    }
+
+    #[test]
+    fn test_format_commits() {
+        let commits: Commits = serde_json::from_str(
+            r#"{"commits": [
+                {"messageHeadline": "Add login", "messageBody": "With a form."},
+                {"messageHeadline": "Fix typo", "messageBody": ""}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            format_commits(&commits),
+            "Add login\n\nWith a form.\n---\nFix typo\n\n\n---"
+        );
+    }
+}
@@ -3,7 +3,10 @@ use std::error::Error;
 use std::{fmt, io};
 
 use crate::git::GitError::GitCommandFailed;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use tempfile::NamedTempFile;
 
 #[derive(Debug)]
 pub enum GitError {
@@ -30,8 +33,27 @@ impl From<io::Error> for GitError {
     }
 }
 
-pub fn get_diff() -> Result<String, GitError> {
-    let output = Command::new("git").args(["diff", "--staged"]).output()?;
+/// Turns glob patterns, relative to the repository root, into pathspecs that
+/// leave the matching files out of a diff.
+fn exclude_pathspecs(exclude: &[String]) -> Vec<String> {
+    if exclude.is_empty() {
+        return Vec::new();
+    }
+
+    let mut pathspecs = vec!["--".to_string(), ":(top)".to_string()];
+    pathspecs.extend(
+        exclude
+            .iter()
+            .map(|pattern| format!(":(top,exclude,glob){}", pattern)),
+    );
+    pathspecs
+}
+
+pub fn get_diff(exclude: &[String]) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args(["diff", "--staged"])
+        .args(exclude_pathspecs(exclude))
+        .output()?;
 
     if output.status.success() {
         if output.stdout.is_empty() {
@@ -44,10 +66,12 @@ pub fn get_diff() -> Result<String, GitError> {
     }
 }
 
-#[allow(dead_code)]
-pub fn get_branch_diff(base_branch: Option<&str>) -> Result<String, GitError> {
+pub fn get_branch_diff(base_branch: Option<&str>, exclude: &[String]) -> Result<String, GitError> {
     let branch = base_branch.unwrap_or("main"); // defaults to main
-    let output = Command::new("git").args(["diff", branch]).output()?;
+    let output = Command::new("git")
+        .args(["diff", branch])
+        .args(exclude_pathspecs(exclude))
+        .output()?;
 
     if output.status.success() {
         if output.stdout.is_empty() {
@@ -63,6 +87,188 @@ pub fn get_branch_diff(base_branch: Option<&str>) -> Result<String, GitError> {
     }
 }
 
+pub fn get_merge_base_diff(base_branch: &str, exclude: &[String]) -> Result<String, GitError> {
+    get_range_diff(base_branch, "HEAD", exclude)
+}
+
+/// Returns the changes `to` made since it forked from `from`, like `git diff from...to`.
+pub fn get_range_diff(from: &str, to: &str, exclude: &[String]) -> Result<String, GitError> {
+    let range = format!("{}...{}", from, to);
+    let output = Command::new("git")
+        .args(["diff", &range])
+        .args(exclude_pathspecs(exclude))
+        .output()?;
+
+    if output.status.success() {
+        if output.stdout.is_empty() {
+            Err(GitError::EmptyDiff)
+        } else {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to get diff against the merge base with '{}'",
+            from
+        )))
+    }
+}
+
+/// Summarizes a diff as `git diff --stat` does, e.g. `["--staged"]` for the
+/// staged changes or `[base]` for a branch.
+pub fn get_diff_stat(diff_args: &[&str], exclude: &[String]) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args(["diff", "--stat", "--color=always"])
+        .args(diff_args)
+        .args(exclude_pathspecs(exclude))
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    } else {
+        Err(GitCommandFailed("Failed to get diff stats".to_string()))
+    }
+}
+
+pub fn get_branch_commits(base_branch: Option<&str>) -> Result<String, GitError> {
+    let branch = base_branch.unwrap_or("main"); // defaults to main
+    get_commits(branch, "HEAD")
+}
+
+/// Lists the messages of the commits in `from..to`, oldest first.
+pub fn get_commits(from: &str, to: &str) -> Result<String, GitError> {
+    let range = format!("{}..{}", from, to);
+    let output = Command::new("git")
+        .args(["log", "--reverse", "--format=%s%n%n%b%n---", &range])
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to get commits in range '{}'",
+            range
+        )))
+    }
+}
+
+pub fn get_repo_root() -> Result<PathBuf, GitError> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .output()?;
+
+    if output.status.success() {
+        Ok(PathBuf::from(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ))
+    } else {
+        Err(GitCommandFailed(
+            "Failed to find the repository root".to_string(),
+        ))
+    }
+}
+
+/// Resolves a path inside the git directory, such as `hooks` or `SQUASH_MSG`.
+/// Hooks honor `core.hooksPath`.
+pub fn get_git_path(name: &str) -> Result<PathBuf, GitError> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", name])
+        .output()?;
+
+    if output.status.success() {
+        Ok(PathBuf::from(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ))
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to find '{}' in the git directory",
+            name
+        )))
+    }
+}
+
+pub fn get_remote_url(remote: &str) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", remote])
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to get the URL of remote '{}'",
+            remote
+        )))
+    }
+}
+
+pub fn get_current_branch() -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(GitCommandFailed(
+            "Failed to get the current branch".to_string(),
+        ))
+    }
+}
+
+pub fn has_upstream() -> Result<bool, GitError> {
+    let output = Command::new("git")
+        .args([
+            "rev-parse",
+            "--abbrev-ref",
+            "--symbolic-full-name",
+            "@{upstream}",
+        ])
+        .output()?;
+
+    Ok(output.status.success())
+}
+
+pub fn push_current_branch() -> Result<(), GitError> {
+    let status = Command::new("git")
+        .args(["push", "--set-upstream", "origin", "HEAD"])
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(GitCommandFailed(
+            "Failed to push the current branch".to_string(),
+        ))
+    }
+}
+
+/// Renders a colored diff between two texts using `git diff --no-index`.
+pub fn diff_texts(old: &str, new: &str) -> Result<String, GitError> {
+    let mut old_file = NamedTempFile::new()?;
+    let mut new_file = NamedTempFile::new()?;
+    writeln!(old_file, "{}", old.trim_end())?;
+    writeln!(new_file, "{}", new.trim_end())?;
+
+    let output = Command::new("git")
+        .args(["diff", "--no-index", "--color=always", "--"])
+        .arg(old_file.path())
+        .arg(new_file.path())
+        .output()?;
+
+    // Exit code 1 means the files differ
+    match output.status.code() {
+        Some(0) | Some(1) => Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            // Drop the file headers, they only name the temporary files
+            .skip_while(|line| !line.contains("@@"))
+            .collect::<Vec<_>>()
+            .join("\n")),
+        _ => Err(GitCommandFailed("Failed to diff texts".to_string())),
+    }
+}
+
 pub fn commit(message: &str) -> Result<(), GitError> {
     let output = Command::new("git")
         .args(["commit", "-m", message])
@@ -74,3 +280,661 @@ pub fn commit(message: &str) -> Result<(), GitError> {
         Err(GitCommandFailed("Failed to commit changes".to_string()))
     }
 }
+
+/// Resolves a revision or range to commit hashes, oldest first. A single
+/// revision names one commit, and ranges are anything with `..`.
+pub fn resolve_commits(revision: &str) -> Result<Vec<String>, GitError> {
+    if revision.contains("..") {
+        return rev_list(&["--reverse", revision]);
+    }
+
+    let output = Command::new("git")
+        .args(["rev-parse", "--verify", &format!("{}^{{commit}}", revision)])
+        .output()?;
+
+    if output.status.success() {
+        Ok(vec![String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .to_string()])
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to resolve '{}' to a commit",
+            revision
+        )))
+    }
+}
+
+fn rev_list(args: &[&str]) -> Result<Vec<String>, GitError> {
+    let output = Command::new("git").arg("rev-list").args(args).output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to list commits with '{}'",
+            args.join(" ")
+        )))
+    }
+}
+
+/// A commit in `git log`, with just what release notes need.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub commit: String,
+    pub parents: Vec<String>,
+    pub subject: String,
+}
+
+/// Lists the commits in `from..to`, merges included, oldest first.
+pub fn get_log(from: &str, to: &str) -> Result<Vec<LogEntry>, GitError> {
+    let output = Command::new("git")
+        .args([
+            "log",
+            "--reverse",
+            "--format=%H%x1f%P%x1f%s",
+            &format!("{}..{}", from, to),
+        ])
+        .output()?;
+
+    if output.status.success() {
+        Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to list the commits in '{}..{}'",
+            from, to
+        )))
+    }
+}
+
+fn parse_log(log: &str) -> Vec<LogEntry> {
+    log.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\x1f');
+            Some(LogEntry {
+                commit: fields.next()?.to_string(),
+                parents: fields
+                    .next()?
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect(),
+                subject: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn is_tag(name: &str) -> bool {
+    Command::new("git")
+        .args([
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("refs/tags/{}", name),
+        ])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Lists the tags reachable from HEAD.
+pub fn get_merged_tags() -> Result<Vec<String>, GitError> {
+    let output = Command::new("git")
+        .args(["tag", "--merged", "HEAD"])
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    } else {
+        Err(GitCommandFailed("Failed to list tags".to_string()))
+    }
+}
+
+/// Creates an annotated tag on HEAD.
+pub fn create_tag(name: &str, message: &str) -> Result<(), GitError> {
+    let status = Command::new("git")
+        .args(["tag", "--annotate", name, "--message", message])
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(GitCommandFailed(format!("Failed to create tag '{}'", name)))
+    }
+}
+
+/// Returns the committer date of a revision as YYYY-MM-DD.
+pub fn get_commit_date(revision: &str) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args(["log", "-1", "--format=%cs", revision])
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to get the date of '{}'",
+            revision
+        )))
+    }
+}
+
+/// Returns the changes a commit introduced, as `git show` prints them.
+pub fn get_commit_diff(commit: &str, exclude: &[String]) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args(["show", "--format=", "--patch", commit])
+        .args(exclude_pathspecs(exclude))
+        .output()?;
+
+    if output.status.success() {
+        if output.stdout.is_empty() {
+            Err(GitError::EmptyDiff)
+        } else {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to get the diff of commit '{}'",
+            commit
+        )))
+    }
+}
+
+pub fn get_commit_stat(commit: &str) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args(["show", "--format=", "--stat", "--color=always", commit])
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to get the stats of commit '{}'",
+            commit
+        )))
+    }
+}
+
+pub fn get_commit_message(commit: &str) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args(["log", "-1", "--format=%B", commit])
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to get the message of commit '{}'",
+            commit
+        )))
+    }
+}
+
+pub fn rev_parse(revision: &str) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet", revision])
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to resolve '{}'",
+            revision
+        )))
+    }
+}
+
+pub fn is_ancestor(commit: &str, of: &str) -> Result<bool, GitError> {
+    let status = Command::new("git")
+        .args(["merge-base", "--is-ancestor", commit, of])
+        .status()?;
+
+    Ok(status.success())
+}
+
+/// Checks for staged or unstaged changes to tracked files.
+pub fn has_uncommitted_changes() -> Result<bool, GitError> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()?;
+
+    if output.status.success() {
+        Ok(!output.stdout.is_empty())
+    } else {
+        Err(GitCommandFailed(
+            "Failed to get the status of the working tree".to_string(),
+        ))
+    }
+}
+
+/// Replaces the message of HEAD, leaving any staged changes out of it.
+pub fn amend_message(message: &str) -> Result<(), GitError> {
+    let status = Command::new("git")
+        .args(["commit", "--amend", "--only", "--no-verify", "-m", message])
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(GitCommandFailed("Failed to amend the commit".to_string()))
+    }
+}
+
+/// Returns the staged changes as a patch that `git apply` can take back,
+/// with binary files included and renames as a deletion and an addition.
+/// The paths always have the `a/` and `b/` prefixes, whatever `diff.noprefix`
+/// or `diff.mnemonicPrefix` say.
+pub fn get_staged_patch() -> Result<String, GitError> {
+    let output = Command::new("git")
+        .args([
+            "diff",
+            "--staged",
+            "--binary",
+            "--no-renames",
+            "--no-color",
+            "--no-ext-diff",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+        ])
+        .output()?;
+
+    if output.status.success() {
+        if output.stdout.is_empty() {
+            Err(GitError::EmptyDiff)
+        } else {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+    } else {
+        Err(GitCommandFailed("Failed to get diff".to_string()))
+    }
+}
+
+/// Lists the staged files, leaving out the ones matching `exclude`.
+pub fn get_staged_files(exclude: &[String]) -> Result<Vec<String>, GitError> {
+    let output = Command::new("git")
+        .args(["diff", "--staged", "--name-only", "--no-renames"])
+        .args(exclude_pathspecs(exclude))
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    } else {
+        Err(GitCommandFailed("Failed to list staged files".to_string()))
+    }
+}
+
+/// Resets the index, or the given index file, to HEAD. The working tree is
+/// left alone.
+pub fn reset_index(index_file: Option<&Path>) -> Result<(), GitError> {
+    let tree = if rev_parse("HEAD").is_ok() {
+        "HEAD"
+    } else {
+        // Before the first commit there is nothing to reset to
+        "--empty"
+    };
+
+    let mut command = Command::new("git");
+    command.args(["read-tree", tree]);
+    if let Some(index_file) = index_file {
+        command.env("GIT_INDEX_FILE", index_file);
+    }
+
+    if command.status()?.success() {
+        Ok(())
+    } else {
+        Err(GitCommandFailed("Failed to reset the index".to_string()))
+    }
+}
+
+/// Stages a patch with `git apply --cached`, into the given index file if any.
+pub fn apply_cached(patch: &str, index_file: Option<&Path>) -> Result<(), GitError> {
+    let mut command = Command::new("git");
+    command
+        .args(["apply", "--cached", "-"])
+        .stdin(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    if let Some(index_file) = index_file {
+        command.env("GIT_INDEX_FILE", index_file);
+    }
+
+    let mut child = command.spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(patch.as_bytes())?;
+    let output = child.wait_with_output()?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(GitCommandFailed(format!(
+            "Failed to stage the patch: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Builds a rebase todo that picks every commit and amends the message of
+/// the reworded ones right after they are picked.
+fn rebase_todo(commits: &[String], messages: &[(String, PathBuf)]) -> String {
+    let mut todo = String::new();
+
+    for commit in commits {
+        todo.push_str(&format!("pick {}\n", commit));
+        if let Some((_, path)) = messages.iter().find(|(reworded, _)| reworded == commit) {
+            todo.push_str(&format!(
+                "exec git commit --amend --only --no-verify --allow-empty -F {}\n",
+                quote_path(path)
+            ));
+        }
+    }
+
+    todo
+}
+
+/// Rewrites the messages of commits on the current branch with a scripted
+/// interactive rebase. Refuses to run on a dirty tree or across merges.
+pub fn reword_commits(messages: &[(String, String)]) -> Result<(), GitError> {
+    if has_uncommitted_changes()? {
+        return Err(GitCommandFailed(
+            "The working tree has uncommitted changes, commit or stash them first".to_string(),
+        ));
+    }
+
+    let Some((oldest, _)) = messages.first() else {
+        return Ok(());
+    };
+    // The oldest commit may be the root, which has no parent to rebase onto
+    let base = rev_parse(&format!("{}^", oldest)).ok();
+    let range = match &base {
+        Some(base) => format!("{}..HEAD", base),
+        None => "HEAD".to_string(),
+    };
+
+    if !rev_list(&["--merges", &range])?.is_empty() {
+        return Err(GitCommandFailed(
+            "Rewording across merge commits is not supported".to_string(),
+        ));
+    }
+    let commits = rev_list(&["--reverse", &range])?;
+
+    // The messages live in the git directory, so they are still there when the
+    // rebase stops and is continued later. Its last step removes them.
+    let dir = get_git_path("gcmgen-reword")?;
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    std::fs::create_dir_all(&dir)?;
+    // The rebase runs its commands from the top of the worktree
+    let dir = dir.canonicalize()?;
+    let mut message_files = Vec::new();
+    for (index, (commit, message)) in messages.iter().enumerate() {
+        let path = dir.join(format!("message-{}", index));
+        std::fs::write(&path, message)?;
+        message_files.push((commit.clone(), path));
+    }
+    let mut todo = rebase_todo(&commits, &message_files);
+    todo.push_str(&format!("exec rm -rf {}\n", quote_path(&dir)));
+    let todo_path = dir.join("todo");
+    std::fs::write(&todo_path, todo)?;
+
+    let mut rebase = Command::new("git");
+    rebase
+        .args(["rebase", "--interactive", "--no-autosquash"])
+        .env("GIT_SEQUENCE_EDITOR", sequence_editor(&todo_path));
+    match &base {
+        Some(base) => rebase.arg(base),
+        None => rebase.arg("--root"),
+    };
+
+    if rebase.status()?.success() {
+        Ok(())
+    } else {
+        Err(GitCommandFailed(
+            "The rebase stopped. Resolve it and run 'git rebase --continue', or undo it with 'git rebase --abort'".to_string(),
+        ))
+    }
+}
+
+/// A sequence editor command that replaces the todo git offers with ours.
+fn sequence_editor(todo: &Path) -> String {
+    format!("cp {}", quote_path(todo))
+}
+
+/// Quotes a path for the shell that runs rebase commands.
+fn quote_path(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', r"'\''"))
+}
+
+/// Lists the files with unstaged changes, untracked ones included, relative
+/// to the repository root.
+pub fn get_unstaged_files() -> Result<Vec<String>, GitError> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain=v1", "-z", "--untracked-files=all"])
+        .output()?;
+
+    if output.status.success() {
+        Ok(parse_unstaged_files(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    } else {
+        Err(GitCommandFailed("Failed to get the status".to_string()))
+    }
+}
+
+/// Picks the files with work tree changes from `git status --porcelain -z`.
+fn parse_unstaged_files(status: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut entries = status.split('\0').filter(|entry| !entry.is_empty());
+
+    while let Some(entry) = entries.next() {
+        let (Some(code), Some(path)) = (entry.get(..2), entry.get(3..)) else {
+            continue;
+        };
+        // Renames and copies are followed by the original path
+        if code.contains(['R', 'C']) {
+            entries.next();
+        }
+        if code == "??" || !code.ends_with(' ') {
+            files.push(path.to_string());
+        }
+    }
+
+    files
+}
+
+/// Stages the given files, with paths relative to the repository root.
+pub fn stage_files(files: &[String]) -> Result<(), GitError> {
+    let status = Command::new("git")
+        .args(["add", "--"])
+        .args(files.iter().map(|file| format!(":(top,literal){}", file)))
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(GitCommandFailed("Failed to stage files".to_string()))
+    }
+}
+
+/// Stages every change to tracked files, like `git commit -a`.
+pub fn stage_tracked() -> Result<(), GitError> {
+    let status = Command::new("git").args(["add", "--update"]).status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(GitCommandFailed("Failed to stage changes".to_string()))
+    }
+}
+
+/// Writes the index as a tree, so it can be put back with `restore_index`.
+pub fn write_index_tree() -> Result<String, GitError> {
+    let output = Command::new("git").arg("write-tree").output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(GitCommandFailed(
+            "Failed to save the index, resolve any conflicts first".to_string(),
+        ))
+    }
+}
+
+/// Puts back an index saved by `write_index_tree`. The working tree is left alone.
+pub fn restore_index(tree: &str) -> Result<(), GitError> {
+    if Command::new("git")
+        .args(["read-tree", tree])
+        .status()?
+        .success()
+    {
+        Ok(())
+    } else {
+        Err(GitCommandFailed("Failed to restore the index".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::Patch;
+    use serial_test::serial;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_rebase_todo_amends_reworded_commits() {
+        let commits = vec!["aaa".to_string(), "bbb".to_string(), "ccc".to_string()];
+        let messages = vec![("bbb".to_string(), PathBuf::from("/tmp/it's/message-0"))];
+
+        assert_eq!(
+            rebase_todo(&commits, &messages),
+            "pick aaa\n\
+             pick bbb\n\
+             exec git commit --amend --only --no-verify --allow-empty -F '/tmp/it'\\''s/message-0'\n\
+             pick ccc\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_unstaged_files_skips_staged_only_changes() {
+        let status = "M  staged.rs\0 M modified.rs\0MM both.rs\0 D deleted.rs\0\
+                      R  new.rs\0old.rs\0?? notes/todo.md\0";
+
+        assert_eq!(
+            parse_unstaged_files(status),
+            vec!["modified.rs", "both.rs", "deleted.rs", "notes/todo.md"]
+        );
+    }
+
+    #[test]
+    fn test_parse_log_reads_parents() {
+        let log = "aaa\x1f\x1fInitial commit\n\
+                   ccc\x1faaa bbb\x1fMerge pull request #2 from a/b\n";
+
+        assert_eq!(
+            parse_log(log),
+            vec![
+                LogEntry {
+                    commit: "aaa".to_string(),
+                    parents: vec![],
+                    subject: "Initial commit".to_string(),
+                },
+                LogEntry {
+                    commit: "ccc".to_string(),
+                    parents: vec!["aaa".to_string(), "bbb".to_string()],
+                    subject: "Merge pull request #2 from a/b".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_staged_patch_ignores_noprefix() {
+        let repo = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(repo.path())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "--quiet"]);
+        git(&["config", "diff.noprefix", "true"]);
+        fs::write(repo.path().join("notes.txt"), "hello\n").unwrap();
+        git(&["add", "notes.txt"]);
+
+        let cwd = env::current_dir().unwrap();
+        env::set_current_dir(repo.path()).unwrap();
+        let patch = get_staged_patch();
+        env::set_current_dir(cwd).unwrap();
+
+        let patch = patch.unwrap();
+        assert!(patch.starts_with("diff --git a/notes.txt b/notes.txt\n"));
+        assert_eq!(Patch::parse(&patch).files[0].path, "notes.txt");
+    }
+
+    #[test]
+    #[serial]
+    fn test_reword_commits_keeps_messages_when_the_rebase_stops() {
+        let repo = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(repo.path())
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(&["init", "--quiet"]);
+        git(&["config", "user.name", "Test"]);
+        git(&["config", "user.email", "test@example.com"]);
+        for name in ["a", "b", "c"] {
+            fs::write(repo.path().join(name), name).unwrap();
+            git(&["add", name]);
+            git(&["commit", "--quiet", "-m", &format!("Add {}", name)]);
+        }
+        let messages = vec![
+            // An empty message fails the amend, which stops the rebase
+            (git(&["rev-parse", "HEAD~1"]), String::new()),
+            (git(&["rev-parse", "HEAD"]), "Add the c file".to_string()),
+        ];
+
+        let cwd = env::current_dir().unwrap();
+        env::set_current_dir(repo.path()).unwrap();
+        let reworded = reword_commits(&messages);
+        env::set_current_dir(cwd).unwrap();
+        assert!(reworded.is_err());
+
+        git(&[
+            "commit",
+            "--amend",
+            "--only",
+            "--quiet",
+            "-m",
+            "Add the b file",
+        ]);
+        git(&["rebase", "--continue"]);
+
+        assert_eq!(
+            git(&["log", "--format=%s"]),
+            "Add the c file\nAdd the b file\nAdd a"
+        );
+        assert!(!repo.path().join(".git/gcmgen-reword").exists());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::forge::PullRequestOptions;
+use std::error::Error;
+use std::process::Command;
+
+fn build_create_args(
+    title: &str,
+    description: &str,
+    branch: &str,
+    options: &PullRequestOptions,
+) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "mr",
+        "create",
+        "--title",
+        title,
+        "--description",
+        description,
+        "--target-branch",
+        branch,
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+
+    if options.draft {
+        args.push("--draft".to_string());
+    }
+    if !options.reviewers.is_empty() {
+        args.extend(["--reviewer".to_string(), options.reviewers.join(",")]);
+    }
+    if !options.labels.is_empty() {
+        args.extend(["--label".to_string(), options.labels.join(",")]);
+    }
+    if !options.assignees.is_empty() {
+        args.extend(["--assignee".to_string(), options.assignees.join(",")]);
+    }
+    if let Some(milestone) = &options.milestone {
+        args.extend(["--milestone".to_string(), milestone.clone()]);
+    }
+    if options.opens_browser() {
+        args.push("--web".to_string());
+    } else {
+        // Skip glab's interactive confirmation
+        args.push("--yes".to_string());
+    }
+
+    args
+}
+
+/// Creates a merge request with `glab`. Returns the URL of the created merge request,
+/// or `None` when the creation page was opened in the browser.
+pub fn create_merge_request(
+    title: &str,
+    description: &str,
+    base_branch: Option<&str>,
+    options: &PullRequestOptions,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let branch = base_branch.unwrap_or("main"); // main is default
+
+    let mut command = Command::new("glab");
+    command.args(build_create_args(title, description, branch, options));
+
+    if options.opens_browser() {
+        let status = command.status()?;
+
+        return if status.success() {
+            println!("Merge request creation page opened in your browser.");
+            Ok(None)
+        } else {
+            Err(Box::new(std::io::Error::other(
+                "Failed to create merge request",
+            )))
+        };
+    }
+
+    let output = command.output()?;
+
+    if output.status.success() {
+        // glab prints the URL of the new merge request as the last line
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines().last().map(|url| url.trim().to_string()))
+    } else {
+        Err(Box::new(std::io::Error::other(format!(
+            "Failed to create merge request: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_create_args_confirms_without_web() {
+        let args = build_create_args("Title", "Body", "main", &PullRequestOptions::default());
+
+        assert_eq!(
+            args,
+            vec![
+                "mr",
+                "create",
+                "--title",
+                "Title",
+                "--description",
+                "Body",
+                "--target-branch",
+                "main",
+                "--yes"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_create_args_joins_repeated_values() {
+        let options = PullRequestOptions {
+            web: true,
+            draft: true,
+            reviewers: vec!["alice".to_string(), "bob".to_string()],
+            labels: vec!["bug".to_string(), "backend".to_string()],
+            assignees: Vec::new(),
+            milestone: None,
+        };
+
+        let args = build_create_args("Title", "Body", "develop", &options);
+        assert_eq!(
+            args[8..],
+            [
+                "--draft",
+                "--reviewer",
+                "alice,bob",
+                "--label",
+                "bug,backend",
+                "--yes"
+            ]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Stores API tokens in the OS keyring through the platform's command line tools:
+//! `secret-tool` (Secret Service) on Linux and `security` (Keychain) on macOS.
+
+use std::io;
+use std::process::Command;
+
+static KEYRING_SERVICE: &str = "gcmgen";
+
+#[cfg(target_os = "macos")]
+pub fn get_password(account: &str) -> Result<String, io::Error> {
+    let output = Command::new("security")
+        .args([
+            "find-generic-password",
+            "-s",
+            KEYRING_SERVICE,
+            "-a",
+            account,
+            "-w",
+        ])
+        .output()?;
+
+    read_password(output, account)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn get_password(account: &str) -> Result<String, io::Error> {
+    let output = Command::new("secret-tool")
+        .args(["lookup", "service", KEYRING_SERVICE, "account", account])
+        .output()?;
+
+    read_password(output, account)
+}
+
+fn read_password(output: std::process::Output, account: &str) -> Result<String, io::Error> {
+    let password = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    if output.status.success() && !password.is_empty() {
+        Ok(password)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No token for '{}' found in the keyring", account),
+        ))
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn set_password(account: &str, password: &str) -> Result<(), io::Error> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    // With -w last and no value, security prompts for the password and its
+    // confirmation on stdin, so it never shows up in the process list
+    let mut child = Command::new("security")
+        .args([
+            "add-generic-password",
+            "-U",
+            "-s",
+            KEYRING_SERVICE,
+            "-a",
+            account,
+            "-w",
+        ])
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(format!("{}\n{}\n", password, password).as_bytes())?;
+
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(
+            "Failed to store the token in the keychain",
+        ))
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn set_password(account: &str, password: &str) -> Result<(), io::Error> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    // secret-tool reads the secret from stdin so it never shows up in the process list
+    let mut child = Command::new("secret-tool")
+        .args([
+            "store",
+            &format!("--label=gcmgen: {}", account),
+            "service",
+            KEYRING_SERVICE,
+            "account",
+            account,
+        ])
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(password.as_bytes())?;
+
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("Failed to store the token in the keyring"))
+    }
+}
@@ -0,0 +1,131 @@
+//! Upgrades config files written by older versions of gcmgen.
+//!
+//! Every change to the config format bumps [`CURRENT_VERSION`] and adds a
+//! migration that turns the previous version into the new one. Files without a
+//! `version` field are version 0.
+
+use serde_json::{json, Value};
+use std::io;
+
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Migrations by the version they upgrade from.
+const MIGRATIONS: [fn(&mut Value); CURRENT_VERSION as usize] = [v0_to_v1];
+
+/// Version 1 adds the `version` field and stores the provider of every profile,
+/// which version 0 took from the profile's name.
+fn v0_to_v1(config: &mut Value) {
+    if let Some(services) = config.get_mut("services").and_then(Value::as_object_mut) {
+        for (name, service) in services.iter_mut() {
+            let Some(service) = service.as_object_mut() else {
+                continue;
+            };
+            if !service.contains_key("provider") && matches!(name.as_str(), "OpenAI" | "Anthropic")
+            {
+                service.insert("provider".to_string(), json!(name));
+            }
+        }
+    }
+}
+
+pub fn version_of(config: &Value) -> u32 {
+    config
+        .get("version")
+        .and_then(Value::as_u64)
+        .map(|version| version as u32)
+        .unwrap_or(0)
+}
+
+/// Upgrades `config` to the current version in place. Returns the version it
+/// had before, or `None` if it was already current.
+pub fn migrate(config: &mut Value) -> Result<Option<u32>, io::Error> {
+    let version = version_of(config);
+
+    if version > CURRENT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "The config file has version {}, but this gcmgen only supports up to version {}. Please upgrade gcmgen.",
+                version, CURRENT_VERSION
+            ),
+        ));
+    }
+    if version == CURRENT_VERSION {
+        return Ok(None);
+    }
+    if !config.is_object() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The config file is not a JSON object",
+        ));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(config);
+    }
+    config["version"] = json!(CURRENT_VERSION);
+
+    Ok(Some(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiToken, Config, Provider, Task};
+
+    fn load_fixture(fixture: &str) -> (Config, Option<u32>) {
+        let mut value: Value = serde_json::from_str(fixture).unwrap();
+        let migrated_from = migrate(&mut value).unwrap();
+        (serde_json::from_value(value).unwrap(), migrated_from)
+    }
+
+    #[test]
+    fn test_migrate_v0() {
+        let (config, migrated_from) =
+            load_fixture(include_str!("../tests/fixtures/config/v0.json"));
+
+        assert_eq!(migrated_from, Some(0));
+        assert_eq!(config.version, CURRENT_VERSION);
+        assert_eq!(config.default_service, "OpenAI");
+        assert_eq!(config.services["OpenAI"].provider, Some(Provider::OpenAI));
+        assert_eq!(
+            config.services["Anthropic"].provider,
+            Some(Provider::Anthropic)
+        );
+        assert_eq!(
+            config.services["OpenAI"].api_token,
+            ApiToken::Plain("sk-openai-token".to_string())
+        );
+    }
+
+    #[test]
+    fn test_migrate_v0_with_unversioned_fields() {
+        let (config, migrated_from) =
+            load_fixture(include_str!("../tests/fixtures/config/v0_extended.json"));
+
+        assert_eq!(migrated_from, Some(0));
+        assert_eq!(config.services["OpenAI"].provider, Some(Provider::OpenAI));
+        assert_eq!(config.services["work"].provider, Some(Provider::OpenAI));
+        let (name, _, _) = config.resolve_profile(None, Task::PullRequest).unwrap();
+        assert_eq!(name, "work");
+        assert_eq!(config.forges["codeberg"].base_url, "https://codeberg.org");
+        assert_eq!(config.settings.base_branch(), "develop");
+    }
+
+    #[test]
+    fn test_migrate_leaves_current_version_untouched() {
+        let fixture = include_str!("../tests/fixtures/config/v1.json");
+        let (config, migrated_from) = load_fixture(fixture);
+
+        assert_eq!(migrated_from, None);
+        assert_eq!(config.version, 1);
+        assert_eq!(config.services["OpenAI"].provider, Some(Provider::OpenAI));
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_versions() {
+        let mut value = json!({ "version": CURRENT_VERSION + 1 });
+
+        assert!(migrate(&mut value).is_err());
+    }
+}
@@ -1,4 +1,9 @@
-use crate::client::{CommitMessageGenerator, PullRequestGenerator};
+use crate::client::{
+    append_feedback, apply_prefix, build_pr_context, build_tag_prompt, build_template_prompt,
+    check_model_response, insert_system_prompt, parse_model_list, Answer, CommitMessageGenerator,
+    Feedback, Instructions, PullRequestGenerator, BUMP_INSTRUCTIONS, CLASSIFY_INSTRUCTIONS,
+    EXPLAIN_INSTRUCTIONS, SPLIT_INSTRUCTIONS, SQUASH_INSTRUCTIONS,
+};
 use crate::config::ServiceConfig;
 use reqwest::blocking::Client;
 use serde_json::{json, Value};
@@ -8,18 +13,62 @@ pub struct OpenAIClient {
     api_key: String,
     client: Client,
     model: String,
+    instructions: Instructions,
 }
 
 impl OpenAIClient {
-    pub fn new(service_config: &ServiceConfig) -> Self {
-        OpenAIClient {
-            api_key: service_config.api_token.clone(),
+    pub fn new(service_config: &ServiceConfig) -> Result<Self, std::io::Error> {
+        Ok(OpenAIClient {
+            api_key: service_config.api_token.resolve()?,
             model: service_config.model.clone(),
             client: Client::new(),
-        }
+            instructions: Instructions::default(),
+        })
+    }
+
+    /// Sets extra instructions that are sent with the requests they apply to.
+    pub fn with_instructions(mut self, instructions: Instructions) -> Self {
+        self.instructions = instructions;
+        self
     }
 
-    pub fn generate_text(&self, messages: Value) -> Result<String, Box<dyn Error>> {
+    /// Checks that the API key is valid and the configured model exists.
+    pub fn check_model(&self) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .get(format!("https://api.openai.com/v1/models/{}", self.model))
+            .bearer_auth(&self.api_key)
+            .send()?;
+
+        check_model_response(response.status(), &self.model)
+    }
+
+    /// Lists the models the API key has access to.
+    pub fn list_models(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let response = self
+            .client
+            .get("https://api.openai.com/v1/models")
+            .bearer_auth(&self.api_key)
+            .send()?;
+
+        parse_model_list(response.status(), &response.json()?)
+    }
+
+    pub fn generate_text(&self, messages: Value, answer: Answer) -> Result<String, Box<dyn Error>> {
+        Ok(self.generate_texts(messages, 1, answer)?.remove(0))
+    }
+
+    /// Requests `count` alternative completions in a single call.
+    pub fn generate_texts(
+        &self,
+        mut messages: Value,
+        count: usize,
+        answer: Answer,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        if let Some(prompt) = self.instructions.system_prompt(answer) {
+            insert_system_prompt(&mut messages, prompt);
+        }
+
         let response = self
             .client
             .post("https://api.openai.com/v1/chat/completions")
@@ -28,6 +77,7 @@ impl OpenAIClient {
                 "model": &self.model,
                 "messages": messages,
                 "max_tokens": 500,
+                "n": count,
             }))
             .send()?;
 
@@ -35,19 +85,25 @@ impl OpenAIClient {
 
         response_json
             .get("choices")
-            .and_then(|choices| choices.get(0))
-            .and_then(|choice| choice.get("message"))
-            .and_then(|content| content.get("content"))
-            .and_then(|text| text.as_str())
-            .map(|text| text.to_string())
+            .and_then(|choices| choices.as_array())
+            .filter(|choices| !choices.is_empty())
+            .and_then(|choices| {
+                choices
+                    .iter()
+                    .map(|choice| {
+                        choice
+                            .get("message")
+                            .and_then(|content| content.get("content"))
+                            .and_then(|text| text.as_str())
+                            .map(|text| text.to_string())
+                    })
+                    .collect::<Option<Vec<String>>>()
+            })
             .ok_or_else(|| {
-                Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!(
-                        "Failed to generate PR title. Unexpected response format: {}",
-                        response_json
-                    ),
-                )) as Box<dyn Error>
+                Box::new(std::io::Error::other(format!(
+                    "Failed to generate text. Unexpected response format: {}",
+                    response_json
+                ))) as Box<dyn Error>
             })
     }
 }
@@ -56,42 +112,47 @@ impl PullRequestGenerator for OpenAIClient {
     fn generate_pr_title(
         &self,
         diff: &str,
+        commits: &str,
         prefix: Option<&String>,
+        feedback: &[Feedback],
     ) -> Result<String, Box<dyn Error>> {
-        let messages = json!([
+        let mut messages = json!([
             {
                 "role": "system",
                 "content": "You are a helpful assistant specialized in writing concise GitHub pull request titles."
             },
             {
                 "role": "user",
-                "content": format!("Here is a git diff:\n\n{}", diff)
+                "content": build_pr_context(diff, commits)
             },
             {
                 "role": "assistant",
-                "content": "Generate a concise and meaningful title for a GitHub pull request based on the provided git diff."
+                "content": "Generate a concise and meaningful title for a GitHub pull request based on the provided commit messages and git diff."
             }
         ]);
 
-        let title = self.generate_text(messages)?;
+        append_feedback(&mut messages, feedback);
 
-        let final_message = if let Some(prefix) = prefix {
-            format!("{} {}", prefix, title.trim())
-        } else {
-            title.trim().to_string()
-        };
+        let title = self.generate_text(messages, Answer::Message)?;
 
-        Ok(final_message)
+        Ok(apply_prefix(&title, prefix))
     }
-    fn generate_pr_description(&self, diff: &str) -> Result<String, Box<dyn Error>> {
-        let messages = json!([
+    fn generate_pr_description(
+        &self,
+        diff: &str,
+        commits: &str,
+        template: Option<&str>,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn Error>> {
+        let mut messages = json!([
             {
                 "role": "system",
                 "content": "You are a helpful assistant specialized in writing detailed GitHub pull request descriptions."
             },
             {
                 "role": "assistant",
-                "content": "Generate a detailed and meaningful description for a GitHub pull request based on the provided git diff. \n\
+                "content": "Generate a detailed and meaningful description for a GitHub pull request based on the provided commit messages and git diff. \n\
+                Use the commit messages to understand the intent behind the changes.\n\
                 Only answer with the description, nothing else. The description will be read by engineers, so keep it concise and meaningful.\n\
                 Don't bloat it. Keep the response under 500 tokens. \n\
                 Don't include any How To's or comments about future work. \n\
@@ -99,23 +160,51 @@ impl PullRequestGenerator for OpenAIClient {
             },
             {
                 "role": "user",
-                "content": format!("Here is a git diff:\n\n{}", diff)
+                "content": build_pr_context(diff, commits)
             },
         ]);
 
-        let description = self.generate_text(messages)?;
+        if let Some(template) = template {
+            messages.as_array_mut().unwrap().push(json!({
+                "role": "user",
+                "content": build_template_prompt(template)
+            }));
+        }
+
+        append_feedback(&mut messages, feedback);
+
+        let description = self.generate_text(messages, Answer::Other)?;
 
         Ok(description.trim().to_string())
     }
+
+    fn explain_changes(&self, diff: &str, commits: &str) -> Result<String, Box<dyn Error>> {
+        let messages = json!([
+            {
+                "role": "system",
+                "content": "You are a helpful assistant specialized in reviewing code changes."
+            },
+            {
+                "role": "user",
+                "content": format!("{}\n\n{}", build_pr_context(diff, commits), EXPLAIN_INSTRUCTIONS)
+            }
+        ]);
+
+        let explanation = self.generate_text(messages, Answer::Other)?;
+
+        Ok(explanation.trim().to_string())
+    }
 }
 
 impl CommitMessageGenerator for OpenAIClient {
-    fn generate_commit_message(
+    fn generate_commit_messages(
         &self,
         diff: &str,
         prefix: Option<&String>,
-    ) -> Result<String, Box<dyn Error>> {
-        let messages = json!([
+        count: usize,
+        feedback: &[Feedback],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut messages = json!([
             {
                 "role": "system",
                 "content": "You are a helpful assistant specialized in writing concise and meaningful git commit messages."
@@ -131,14 +220,114 @@ impl CommitMessageGenerator for OpenAIClient {
             }
         ]);
 
-        let message = self.generate_text(messages)?;
+        append_feedback(&mut messages, feedback);
+
+        let messages = self.generate_texts(messages, count, Answer::Message)?;
+
+        Ok(messages
+            .iter()
+            .map(|message| apply_prefix(message, prefix))
+            .collect())
+    }
+
+    fn generate_squash_message(
+        &self,
+        diff: &str,
+        commits: &str,
+        prefix: Option<&String>,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn Error>> {
+        let mut messages = json!([
+            {
+                "role": "system",
+                "content": "You are a helpful assistant specialized in writing concise and meaningful git commit messages."
+            },
+            {
+                "role": "user",
+                "content": format!("{}\n\n{}", build_pr_context(diff, commits), SQUASH_INSTRUCTIONS)
+            }
+        ]);
+
+        append_feedback(&mut messages, feedback);
+
+        let message = self.generate_text(messages, Answer::Message)?;
+
+        Ok(apply_prefix(&message, prefix))
+    }
+
+    fn generate_commit_plan(
+        &self,
+        hunks: &str,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn Error>> {
+        let mut messages = json!([
+            {
+                "role": "system",
+                "content": "You are a helpful assistant specialized in writing concise and meaningful git commit messages."
+            },
+            {
+                "role": "user",
+                "content": format!("Here are the staged hunks:\n\n{}\n\n{}", hunks, SPLIT_INSTRUCTIONS)
+            }
+        ]);
+
+        append_feedback(&mut messages, feedback);
+
+        self.generate_text(messages, Answer::Message)
+    }
+
+    fn classify_changes(&self, changes: &str) -> Result<String, Box<dyn Error>> {
+        let messages = json!([
+            {
+                "role": "system",
+                "content": "You are a helpful assistant specialized in writing release notes."
+            },
+            {
+                "role": "user",
+                "content": format!("Here are the changes:\n\n{}\n\n{}", changes, CLASSIFY_INSTRUCTIONS)
+            }
+        ]);
+
+        self.generate_text(messages, Answer::Other)
+    }
+
+    fn suggest_bump(&self, diff: &str, commits: &str) -> Result<String, Box<dyn Error>> {
+        let messages = json!([
+            {
+                "role": "system",
+                "content": "You are a helpful assistant specialized in semantic versioning."
+            },
+            {
+                "role": "user",
+                "content": format!("{}\n\n{}", build_pr_context(diff, commits), BUMP_INSTRUCTIONS)
+            }
+        ]);
+
+        self.generate_text(messages, Answer::Other)
+    }
+
+    fn generate_tag_message(
+        &self,
+        diff: &str,
+        commits: &str,
+        version: &str,
+        feedback: &[Feedback],
+    ) -> Result<String, Box<dyn Error>> {
+        let mut messages = json!([
+            {
+                "role": "system",
+                "content": "You are a helpful assistant specialized in writing release notes."
+            },
+            {
+                "role": "user",
+                "content": format!("{}\n\n{}", build_pr_context(diff, commits), build_tag_prompt(version))
+            }
+        ]);
+
+        append_feedback(&mut messages, feedback);
 
-        let final_message = if let Some(prefix) = prefix {
-            format!("{} {}", prefix, message.trim())
-        } else {
-            message.trim().to_string()
-        };
+        let message = self.generate_text(messages, Answer::Other)?;
 
-        Ok(final_message)
+        Ok(message.trim().to_string())
     }
 }
@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+
+/// A file in a unified diff, split into its header and hunks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiff {
+    pub path: String,
+    header: String,
+    hunks: Vec<String>,
+}
+
+/// The smallest piece of a diff that can go into a commit on its own: a hunk,
+/// or a whole file when it has no hunks, such as a binary file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    file: usize,
+    hunk: Option<usize>,
+}
+
+/// A unified diff as `git diff` prints it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    pub files: Vec<FileDiff>,
+}
+
+impl Patch {
+    pub fn parse(diff: &str) -> Patch {
+        let mut files: Vec<FileDiff> = Vec::new();
+
+        for line in diff.split_inclusive('\n') {
+            if line.starts_with("diff --git ") {
+                files.push(FileDiff {
+                    path: path_from_diff_line(line),
+                    header: line.to_string(),
+                    hunks: Vec::new(),
+                });
+                continue;
+            }
+            let Some(file) = files.last_mut() else {
+                continue;
+            };
+
+            if line.starts_with("@@") {
+                file.hunks.push(line.to_string());
+            } else if let Some(hunk) = file.hunks.last_mut() {
+                hunk.push_str(line);
+            } else {
+                if let Some(path) = line.strip_prefix("+++ b/") {
+                    file.path = path.trim_end().to_string();
+                }
+                file.header.push_str(line);
+            }
+        }
+
+        Patch { files }
+    }
+
+    /// Lists every unit in the order of the diff.
+    pub fn units(&self) -> Vec<Unit> {
+        self.files
+            .iter()
+            .enumerate()
+            .flat_map(|(file, diff)| match diff.hunks.len() {
+                0 => vec![Unit { file, hunk: None }],
+                hunks => (0..hunks)
+                    .map(|hunk| Unit {
+                        file,
+                        hunk: Some(hunk),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub fn path(&self, unit: Unit) -> &str {
+        &self.files[unit.file].path
+    }
+
+    /// A one-line description of a unit, the path and the hunk header.
+    pub fn describe(&self, unit: Unit) -> String {
+        let file = &self.files[unit.file];
+        match unit.hunk {
+            Some(hunk) => format!(
+                "{} {}",
+                file.path,
+                file.hunks[hunk].lines().next().unwrap_or_default()
+            ),
+            None => file.path.clone(),
+        }
+    }
+
+    /// The full text of a unit, for showing it to the model.
+    pub fn text(&self, unit: Unit) -> &str {
+        let file = &self.files[unit.file];
+        match unit.hunk {
+            Some(hunk) => &file.hunks[hunk],
+            None => &file.header,
+        }
+    }
+
+    /// Builds one patch per group, to be applied in order. Only the first patch
+    /// that touches a file carries its full header, so mode changes and new
+    /// files are applied once.
+    pub fn split(&self, groups: &[Vec<Unit>]) -> Vec<String> {
+        let mut touched = HashSet::new();
+
+        groups
+            .iter()
+            .map(|units| {
+                let mut patch = String::new();
+                for (index, file) in self.files.iter().enumerate() {
+                    let hunks: Vec<&Unit> =
+                        units.iter().filter(|unit| unit.file == index).collect();
+                    if hunks.is_empty() {
+                        continue;
+                    }
+
+                    if touched.insert(index) {
+                        patch.push_str(&file.header);
+                    } else {
+                        patch.push_str(&minimal_header(&file.header));
+                    }
+                    let mut hunks: Vec<usize> = hunks.iter().filter_map(|unit| unit.hunk).collect();
+                    hunks.sort();
+                    for hunk in hunks {
+                        patch.push_str(&file.hunks[hunk]);
+                    }
+                }
+                patch
+            })
+            .collect()
+    }
+}
+
+/// Reads the new path from `diff --git a/old b/new`.
+fn path_from_diff_line(line: &str) -> String {
+    line.trim_end()
+        .rsplit_once(" b/")
+        .map(|(_, path)| path.to_string())
+        .unwrap_or_default()
+}
+
+/// Keeps the lines of a file header that a follow-up patch needs.
+fn minimal_header(header: &str) -> String {
+    header
+        .split_inclusive('\n')
+        .filter(|line| {
+            line.starts_with("diff --git ") || line.starts_with("--- ") || line.starts_with("+++ ")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+old mode 100644
+new mode 100755
+index 1111111..2222222
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,4 @@
++use std::fs;
+ fn a() {}
+ fn b() {}
+ fn c() {}
+@@ -10,3 +11,3 @@ fn d() {
+ fn e() {}
+-fn f() {}
++fn g() {}
+ fn h() {}
+diff --git a/logo.png b/logo.png
+new file mode 100644
+index 0000000..3333333
+Binary files /dev/null and b/logo.png differ
+";
+
+    #[test]
+    fn test_parse_splits_files_and_hunks() {
+        let patch = Patch::parse(DIFF);
+
+        assert_eq!(patch.files.len(), 2);
+        assert_eq!(patch.files[0].path, "src/lib.rs");
+        assert_eq!(patch.files[0].hunks.len(), 2);
+        assert_eq!(patch.files[1].path, "logo.png");
+        assert!(patch.files[1].hunks.is_empty());
+
+        let units = patch.units();
+        assert_eq!(units.len(), 3);
+        assert_eq!(
+            patch.describe(units[1]),
+            "src/lib.rs @@ -10,3 +11,3 @@ fn d() {"
+        );
+        assert_eq!(patch.describe(units[2]), "logo.png");
+    }
+
+    #[test]
+    fn test_split_rebuilds_the_original_diff() {
+        let patch = Patch::parse(DIFF);
+
+        assert_eq!(patch.split(&[patch.units()]), vec![DIFF.to_string()]);
+    }
+
+    #[test]
+    fn test_split_uses_full_header_only_once_per_file() {
+        let patch = Patch::parse(DIFF);
+        let units = patch.units();
+
+        let patches = patch.split(&[vec![units[1], units[2]], vec![units[0]]]);
+
+        assert!(patches[0].contains("new mode 100755"));
+        assert!(patches[0].contains("+fn g() {}"));
+        assert!(patches[0].contains("Binary files /dev/null and b/logo.png differ"));
+        assert!(!patches[1].contains("new mode 100755"));
+        assert!(patches[1].starts_with(
+            "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,4 @@"
+        ));
+    }
+}
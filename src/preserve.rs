@@ -0,0 +1,73 @@
+/// Marks the start of a hand-written section that survives description updates.
+pub static PRESERVE_START: &str = "<!-- gcmgen:preserve -->";
+/// Marks the end of a hand-written section.
+pub static PRESERVE_END: &str = "<!-- /gcmgen:preserve -->";
+
+/// Returns every preserved section in `text`, markers included.
+pub fn extract_preserved_sections(text: &str) -> Vec<String> {
+    let mut sections = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(PRESERVE_START) {
+        let Some(end) = rest[start..].find(PRESERVE_END) else {
+            break;
+        };
+        let end = start + end + PRESERVE_END.len();
+        sections.push(rest[start..end].to_string());
+        rest = &rest[end..];
+    }
+
+    sections
+}
+
+/// Appends the preserved sections of `old` to the regenerated `new` text.
+pub fn merge_preserved_sections(old: &str, new: &str) -> String {
+    let sections = extract_preserved_sections(old);
+
+    if sections.is_empty() {
+        return new.to_string();
+    }
+
+    format!("{}\n\n{}", new.trim_end(), sections.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_preserved_sections_finds_all_sections() {
+        let text = format!(
+            "Intro\n{PRESERVE_START}\nFirst\n{PRESERVE_END}\nMiddle\n{PRESERVE_START}\nSecond\n{PRESERVE_END}"
+        );
+
+        assert_eq!(
+            extract_preserved_sections(&text),
+            vec![
+                format!("{PRESERVE_START}\nFirst\n{PRESERVE_END}"),
+                format!("{PRESERVE_START}\nSecond\n{PRESERVE_END}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_preserved_sections_ignores_unterminated_section() {
+        let text = format!("{PRESERVE_START}\nNever closed");
+
+        assert!(extract_preserved_sections(&text).is_empty());
+    }
+
+    #[test]
+    fn test_merge_preserved_sections_appends_sections_to_new_text() {
+        let old = format!("Old summary\n\n{PRESERVE_START}\nDeploy notes\n{PRESERVE_END}");
+
+        assert_eq!(
+            merge_preserved_sections(&old, "New summary\n"),
+            format!("New summary\n\n{PRESERVE_START}\nDeploy notes\n{PRESERVE_END}")
+        );
+        assert_eq!(
+            merge_preserved_sections("Old summary", "New summary"),
+            "New summary"
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use crate::client::Feedback;
+use crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use inquire::{InquireError, Select, Text};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// What the user can do with a generated commit message or pull request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Accept,
+    Edit,
+    Feedback,
+    Regenerate,
+    Choose,
+    Skip,
+    Quit,
+}
+
+impl Action {
+    /// The key that selects the action in the menu.
+    pub fn key(&self) -> char {
+        match self {
+            Action::Accept => 'y',
+            Action::Edit => 'e',
+            Action::Feedback => 'f',
+            Action::Regenerate => 'r',
+            Action::Choose => 'c',
+            Action::Skip => 's',
+            Action::Quit => 'q',
+        }
+    }
+
+    /// Finds the offered action for a pressed key, in either case.
+    pub fn for_key(actions: &[Action], key: char) -> Option<Action> {
+        actions
+            .iter()
+            .copied()
+            .find(|action| action.key() == key.to_ascii_lowercase())
+    }
+
+    /// The actions offered, with `Choose` only when there is something to choose from.
+    pub fn available(has_alternatives: bool) -> Vec<Action> {
+        let mut actions = vec![
+            Action::Accept,
+            Action::Edit,
+            Action::Feedback,
+            Action::Regenerate,
+        ];
+        if has_alternatives {
+            actions.push(Action::Choose);
+        }
+        actions.push(Action::Quit);
+        actions
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Action::Accept => "Accept",
+            Action::Edit => "Edit, then accept",
+            Action::Feedback => "Give feedback and revise",
+            Action::Regenerate => "Regenerate",
+            Action::Choose => "Choose another candidate",
+            Action::Skip => "Skip, keep the current message",
+            Action::Quit => "Quit",
+        };
+        write!(f, "[{}] {}", self.key(), label)
+    }
+}
+
+/// The outcome of an action, with any text the user entered for it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<T> {
+    Accept,
+    Edited(T),
+    Feedback(String),
+    Regenerate,
+    Select(usize),
+    Quit,
+}
+
+/// What the flow should do after an event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Next<T> {
+    /// Ask for the next action about the current candidate.
+    Prompt,
+    /// Generate more candidates, revising with the feedback if there is any.
+    Generate,
+    Finish(T),
+    Cancel,
+}
+
+/// Keeps every candidate generated so far and the feedback given on them.
+#[derive(Debug)]
+pub struct Review<T> {
+    candidates: Vec<T>,
+    current: usize,
+    feedback: Vec<(T, String)>,
+}
+
+impl<T: Clone> Review<T> {
+    pub fn new() -> Self {
+        Review {
+            candidates: Vec::new(),
+            current: 0,
+            feedback: Vec::new(),
+        }
+    }
+
+    /// Adds new candidates and moves to the first of them.
+    pub fn add_candidates(&mut self, candidates: Vec<T>) {
+        if !candidates.is_empty() {
+            self.current = self.candidates.len();
+            self.candidates.extend(candidates);
+        }
+    }
+
+    pub fn current(&self) -> &T {
+        &self.candidates[self.current]
+    }
+
+    pub fn candidates(&self) -> &[T] {
+        &self.candidates
+    }
+
+    /// Returns the position of the current candidate, counting from 1.
+    pub fn position(&self) -> usize {
+        self.current + 1
+    }
+
+    /// Returns the feedback rounds so far, with the part of each answer that
+    /// `answer` picks out.
+    pub fn feedback(&self, answer: impl Fn(&T) -> &str) -> Vec<Feedback> {
+        self.feedback
+            .iter()
+            .map(|(candidate, feedback)| Feedback {
+                answer: answer(candidate).to_string(),
+                feedback: feedback.clone(),
+            })
+            .collect()
+    }
+
+    pub fn handle(&mut self, event: Event<T>) -> Next<T> {
+        match event {
+            Event::Accept => Next::Finish(self.current().clone()),
+            Event::Edited(edited) => Next::Finish(edited),
+            Event::Feedback(feedback) if feedback.trim().is_empty() => Next::Prompt,
+            Event::Feedback(feedback) => {
+                self.feedback
+                    .push((self.current().clone(), feedback.trim().to_string()));
+                Next::Generate
+            }
+            Event::Regenerate => Next::Generate,
+            Event::Select(index) if index < self.candidates.len() => {
+                self.current = index;
+                Next::Prompt
+            }
+            Event::Select(_) => Next::Prompt,
+            Event::Quit => Next::Cancel,
+        }
+    }
+
+    /// Turns an action into its event, asking for the feedback or candidate it
+    /// needs. `edit` opens the current candidate in the editor, and `summary`
+    /// is the line shown for each candidate when choosing. Skip quits, flows
+    /// that keep the current text handle it before. `prompt_action` only
+    /// returns offered actions, so flows don't handle the others themselves.
+    pub fn event_for(
+        &self,
+        action: Action,
+        edit: impl FnOnce(&T) -> Result<T, Box<dyn Error>>,
+        summary: impl Fn(&T) -> String,
+    ) -> Result<Event<T>, Box<dyn Error>> {
+        Ok(match action {
+            Action::Accept => Event::Accept,
+            Action::Edit => Event::Edited(edit(self.current())?),
+            Action::Feedback => Event::Feedback(prompt_feedback()?),
+            Action::Regenerate => Event::Regenerate,
+            Action::Choose => Event::Select(prompt_candidate(
+                self.candidates.iter().map(summary).collect(),
+                self.current,
+            )?),
+            Action::Skip | Action::Quit => Event::Quit,
+        })
+    }
+}
+
+/// The first line of a message, which sums it up when choosing a candidate.
+pub fn first_line(message: &str) -> String {
+    message.lines().next().unwrap_or_default().to_string()
+}
+
+/// Asks for the next action, which is picked as soon as its key is pressed.
+/// Without a terminal, such as when the input is piped, a line is read instead.
+pub fn prompt_action(actions: Vec<Action>) -> Result<Action, InquireError> {
+    println!("What do you want to do?");
+    for action in &actions {
+        println!("  {}", action);
+    }
+
+    if !io::stdin().is_terminal() {
+        return read_action_line(&actions, io::stdin().lock());
+    }
+
+    print!("Press a key: ");
+    io::stdout().flush()?;
+
+    terminal::enable_raw_mode()?;
+    let action = read_action(&actions);
+    terminal::disable_raw_mode()?;
+
+    match action {
+        Ok(action) => println!("{}", action),
+        Err(_) => println!(),
+    }
+    action
+}
+
+/// Waits for the key of an offered action. Esc cancels and Ctrl-C interrupts,
+/// like the other prompts.
+fn read_action(actions: &[Action]) -> Result<Action, InquireError> {
+    loop {
+        let event::Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind,
+            ..
+        }) = event::read()?
+        else {
+            continue;
+        };
+        if kind == KeyEventKind::Release {
+            continue;
+        }
+
+        match code {
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                return Err(InquireError::OperationInterrupted)
+            }
+            KeyCode::Esc => return Err(InquireError::OperationCanceled),
+            KeyCode::Char(key) => {
+                if let Some(action) = Action::for_key(actions, key) {
+                    return Ok(action);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads lines until one starts with the key of an offered action. The end of
+/// the input cancels.
+fn read_action_line(actions: &[Action], mut input: impl BufRead) -> Result<Action, InquireError> {
+    loop {
+        print!("Type a key and press enter: ");
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            println!();
+            return Err(InquireError::OperationCanceled);
+        }
+        let key = line.trim().chars().next();
+        if let Some(action) = key.and_then(|key| Action::for_key(actions, key)) {
+            return Ok(action);
+        }
+    }
+}
+
+/// Asks which candidate to continue with, showing the first line of each.
+pub fn prompt_candidate(summaries: Vec<String>, current: usize) -> Result<usize, InquireError> {
+    let summaries = summaries
+        .into_iter()
+        .enumerate()
+        .map(|(index, summary)| format!("[{}] {}", index + 1, summary))
+        .collect();
+
+    Ok(Select::new("Choose a candidate:", summaries)
+        .with_starting_cursor(current)
+        .raw_prompt()?
+        .index)
+}
+
+pub fn prompt_feedback() -> Result<String, InquireError> {
+    Text::new("What should change?")
+        .with_placeholder("shorter, mention the migration, don't say refactor, ...")
+        .prompt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(candidates: &[&str]) -> Review<String> {
+        let mut review = Review::new();
+        review.add_candidates(candidates.iter().map(|c| c.to_string()).collect());
+        review
+    }
+
+    #[test]
+    fn test_accept_finishes_with_current_candidate() {
+        let mut review = review(&["Fix typo"]);
+
+        assert_eq!(
+            review.handle(Event::Accept),
+            Next::Finish("Fix typo".to_string())
+        );
+    }
+
+    #[test]
+    fn test_edit_finishes_with_edited_text() {
+        let mut review = review(&["Fix typo"]);
+
+        assert_eq!(
+            review.handle(Event::Edited("Fix typo in README".to_string())),
+            Next::Finish("Fix typo in README".to_string())
+        );
+    }
+
+    #[test]
+    fn test_feedback_is_recorded_against_the_current_candidate() {
+        let mut review = review(&["Refactor parser", "Rework parser"]);
+        review.handle(Event::Select(0));
+
+        assert_eq!(
+            review.handle(Event::Feedback(" don't say refactor ".to_string())),
+            Next::Generate
+        );
+        assert_eq!(
+            review.feedback(|message| message),
+            vec![Feedback {
+                answer: "Refactor parser".to_string(),
+                feedback: "don't say refactor".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_empty_feedback_asks_again() {
+        let mut review = review(&["Fix typo"]);
+
+        assert_eq!(
+            review.handle(Event::Feedback("  ".to_string())),
+            Next::Prompt
+        );
+        assert!(review.feedback(|message| message).is_empty());
+    }
+
+    #[test]
+    fn test_regenerated_candidates_keep_the_previous_ones() {
+        let mut review = review(&["First"]);
+
+        assert_eq!(review.handle(Event::Regenerate), Next::Generate);
+        review.add_candidates(vec!["Second".to_string()]);
+        assert_eq!(review.current(), "Second");
+        assert_eq!(review.position(), 2);
+
+        assert_eq!(review.handle(Event::Select(0)), Next::Prompt);
+        assert_eq!(review.current(), "First");
+        assert_eq!(review.candidates().len(), 2);
+    }
+
+    #[test]
+    fn test_select_out_of_range_keeps_current() {
+        let mut review = review(&["First"]);
+
+        assert_eq!(review.handle(Event::Select(5)), Next::Prompt);
+        assert_eq!(review.current(), "First");
+    }
+
+    #[test]
+    fn test_quit_cancels() {
+        let mut review = review(&["Fix typo"]);
+
+        assert_eq!(review.handle(Event::Quit), Next::Cancel);
+    }
+
+    #[test]
+    fn test_choose_is_only_offered_with_alternatives() {
+        assert!(!Action::available(false).contains(&Action::Choose));
+        assert!(Action::available(true).contains(&Action::Choose));
+        assert_eq!(Action::available(true).last(), Some(&Action::Quit));
+    }
+
+    #[test]
+    fn test_action_keys_are_unique() {
+        let mut actions = Action::available(true);
+        actions.push(Action::Skip);
+        let mut keys: Vec<char> = actions.iter().map(Action::key).collect();
+        keys.sort();
+        keys.dedup();
+
+        assert_eq!(keys.len(), actions.len());
+    }
+
+    #[test]
+    fn test_for_key_only_finds_offered_actions() {
+        let actions = Action::available(false);
+
+        assert_eq!(Action::for_key(&actions, 'y'), Some(Action::Accept));
+        assert_eq!(Action::for_key(&actions, 'R'), Some(Action::Regenerate));
+        assert_eq!(Action::for_key(&actions, 'c'), None);
+        assert_eq!(Action::for_key(&actions, 'x'), None);
+    }
+
+    #[test]
+    fn test_read_action_line_skips_unknown_keys() {
+        let actions = Action::available(false);
+
+        let input = io::Cursor::new("x\n\nRegenerate\n");
+        assert_eq!(
+            read_action_line(&actions, input).unwrap(),
+            Action::Regenerate
+        );
+
+        let input = io::Cursor::new("c\n");
+        assert!(matches!(
+            read_action_line(&actions, input),
+            Err(InquireError::OperationCanceled)
+        ));
+    }
+
+    #[test]
+    fn test_event_for_edits_the_current_candidate() {
+        let review = review(&["Fix typo"]);
+
+        let event = review
+            .event_for(
+                Action::Edit,
+                |message| Ok(format!("{} in README", message)),
+                |message| first_line(message),
+            )
+            .unwrap();
+        assert_eq!(event, Event::Edited("Fix typo in README".to_string()));
+        assert_eq!(
+            review
+                .event_for(
+                    Action::Skip,
+                    |message| Ok(message.clone()),
+                    |message| first_line(message)
+                )
+                .unwrap(),
+            Event::Quit
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// How much a release changes the version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "patch" => Some(Level::Patch),
+            "minor" => Some(Level::Minor),
+            "major" => Some(Level::Major),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Patch => write!(f, "patch"),
+            Level::Minor => write!(f, "minor"),
+            Level::Major => write!(f, "major"),
+        }
+    }
+}
+
+/// A release version, read from a tag such as `v1.2.3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    /// Reads `1.2.3` or `v1.2.3`. Pre-releases and build metadata aren't releases
+    /// to bump from, so they return `None`.
+    pub fn parse(tag: &str) -> Option<Version> {
+        let version = tag.strip_prefix('v').unwrap_or(tag);
+        let mut parts = version.split('.').map(|part| {
+            // Leading zeros aren't allowed, `01` is not a version number
+            if part.len() > 1 && part.starts_with('0') {
+                None
+            } else {
+                part.parse::<u64>().ok()
+            }
+        });
+
+        let version = Version {
+            major: parts.next()??,
+            minor: parts.next()??,
+            patch: parts.next()??,
+        };
+        parts.next().is_none().then_some(version)
+    }
+
+    /// Before 1.0.0 a breaking change only bumps the minor version.
+    pub fn bump(&self, level: Level) -> Version {
+        match level {
+            Level::Major if self.major > 0 => Version {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            Level::Major | Level::Minor => Version {
+                major: self.major,
+                minor: self.minor + 1,
+                patch: 0,
+            },
+            Level::Patch => Version {
+                patch: self.patch + 1,
+                ..*self
+            },
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Finds the highest version among the tags, with its tag.
+pub fn latest_tag(tags: &[String]) -> Option<(&str, Version)> {
+    tags.iter()
+        .filter_map(|tag| Some((tag.as_str(), Version::parse(tag)?)))
+        .max_by_key(|(_, version)| *version)
+}
+
+/// Finds the commits that say they break compatibility, with a `!` after the
+/// type or a `BREAKING CHANGE` footer. Takes the messages as `git::get_commits`
+/// lists them and returns their subjects.
+pub fn find_breaking_commits(commits: &str) -> Vec<String> {
+    commits
+        .split("\n---")
+        .filter_map(|message| {
+            let message = message.trim();
+            let subject = message.lines().next()?;
+            let marked = subject
+                .split_once(": ")
+                .is_some_and(|(kind, _)| kind.ends_with('!') && !kind.contains(' '));
+            let footer = message.lines().any(|line| {
+                line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+            });
+            (marked || footer).then(|| subject.to_string())
+        })
+        .collect()
+}
+
+/// Lists the public items a diff removes without adding them back, such as
+/// `fn parse`, so renames and moves within the diff don't count.
+pub fn find_removed_items(diff: &str) -> Vec<String> {
+    // Public items in Rust, and exports in JavaScript and TypeScript
+    let public_item = Regex::new(
+        r"^([-+])\s*(?:pub|export)\s+(?:(?:default|async|unsafe|const|extern|abstract)\s+)*(fn|struct|enum|trait|mod|const|static|type|union|function|class|interface)\s+([A-Za-z_$][A-Za-z0-9_$]*)",
+    )
+    .expect("the pattern is valid");
+    let mut removed = BTreeSet::new();
+    let mut added = BTreeSet::new();
+
+    for line in diff.lines() {
+        if line.starts_with("---") || line.starts_with("+++") {
+            continue;
+        }
+        if let Some(captures) = public_item.captures(line) {
+            let item = format!("{} {}", &captures[2], &captures[3]);
+            if &captures[1] == "-" {
+                removed.insert(item);
+            } else {
+                added.insert(item);
+            }
+        }
+    }
+
+    removed.difference(&added).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            Version::parse("v1.2.3"),
+            Some(Version {
+                major: 1,
+                minor: 2,
+                patch: 3
+            })
+        );
+        assert_eq!(Version::parse("0.10.0").map(|v| v.minor), Some(10));
+        assert_eq!(Version::parse("v1.2.3-rc.1"), None);
+        assert_eq!(Version::parse("v1.2"), None);
+        assert_eq!(Version::parse("v01.2.3"), None);
+        assert_eq!(Version::parse("release"), None);
+    }
+
+    #[test]
+    fn test_bump() {
+        let version = Version::parse("1.4.2").unwrap();
+        assert_eq!(version.bump(Level::Major).to_string(), "2.0.0");
+        assert_eq!(version.bump(Level::Minor).to_string(), "1.5.0");
+        assert_eq!(version.bump(Level::Patch).to_string(), "1.4.3");
+
+        let version = Version::parse("0.3.1").unwrap();
+        assert_eq!(version.bump(Level::Major).to_string(), "0.4.0");
+    }
+
+    #[test]
+    fn test_latest_tag_compares_versions() {
+        let tags = vec![
+            "v1.9.0".to_string(),
+            "v1.10.0".to_string(),
+            "v2.0.0-beta.1".to_string(),
+            "nightly".to_string(),
+        ];
+
+        assert_eq!(latest_tag(&tags).map(|(tag, _)| tag), Some("v1.10.0"));
+        assert_eq!(latest_tag(&[]), None);
+    }
+
+    #[test]
+    fn test_find_breaking_commits() {
+        let commits = "feat!: drop the --ls flag\n\n\n---\n\
+                       fix: handle empty diffs\n\nMentions BREAKING CHANGE: in passing\n---\n\
+                       refactor(config): rename keys\n\nBREAKING CHANGE: api_key is now token\n---\n\
+                       Fix this!: really\n\n\n---";
+
+        assert_eq!(
+            find_breaking_commits(commits),
+            vec!["feat!: drop the --ls flag", "refactor(config): rename keys"]
+        );
+    }
+
+    #[test]
+    fn test_find_removed_items_ignores_moves() {
+        let diff = "\
+--- a/src/lib.rs
++++ b/src/lib.rs
+-pub fn parse(input: &str) -> Config {
+-pub(crate) fn helper() {}
+-pub struct Moved;
++pub struct Moved;
+-    pub const fn limit() -> usize {
+--- a/web/index.ts
++++ b/web/index.ts
+-export default class Widget {
+";
+
+        assert_eq!(
+            find_removed_items(diff),
+            vec!["class Widget", "fn limit", "fn parse"]
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use crate::client::Instructions;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::{fs, io};
+
+/// Name of the per-repository config file in the repository root.
+pub static REPO_CONFIG_FILE: &str = ".gcmgen.toml";
+
+/// Conventional commit rules the generated messages must follow.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConventionalCommits {
+    pub enabled: bool,
+    /// Allowed types, e.g. `feat` and `fix`. Any type is allowed when empty.
+    pub types: Vec<String>,
+    /// Allowed scopes. Any scope is allowed when empty.
+    pub scopes: Vec<String>,
+    pub require_scope: bool,
+}
+
+impl Default for ConventionalCommits {
+    fn default() -> Self {
+        ConventionalCommits {
+            enabled: true,
+            types: Vec::new(),
+            scopes: Vec::new(),
+            require_scope: false,
+        }
+    }
+}
+
+/// Settings that shape the generated text. They can be set in the user config
+/// and in a `.gcmgen.toml` committed to the repository, which never holds secrets.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Extra instructions on the style of the generated text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_style: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conventional_commits: Option<ConventionalCommits>,
+    /// Regex that finds the ticket in the branch name. The first capture group is
+    /// used if there is one, otherwise the whole match.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_regex: Option<String>,
+    /// Glob patterns, relative to the repository root, of files left out of diffs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_branch: Option<String>,
+    /// Path of the pull request template, relative to the repository root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pr_template: Option<String>,
+}
+
+impl Settings {
+    pub fn is_empty(&self) -> bool {
+        *self == Settings::default()
+    }
+
+    /// Reads the `.gcmgen.toml` in `root`, if there is one.
+    pub fn load_repo(root: &Path) -> Result<Option<Self>, io::Error> {
+        let path = root.join(REPO_CONFIG_FILE);
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        toml::from_str(&contents).map(Some).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid {}: {}", path.display(), e),
+            )
+        })
+    }
+
+    /// Layers `other` on top of these settings. Values set in `other` win, and
+    /// ignore patterns from both are kept.
+    pub fn merge(mut self, other: Settings) -> Self {
+        self.prompt_style = other.prompt_style.or(self.prompt_style);
+        self.conventional_commits = other.conventional_commits.or(self.conventional_commits);
+        self.ticket_regex = other.ticket_regex.or(self.ticket_regex);
+        self.ignore.extend(other.ignore);
+        self.base_branch = other.base_branch.or(self.base_branch);
+        self.pr_template = other.pr_template.or(self.pr_template);
+        self
+    }
+
+    pub fn base_branch(&self) -> &str {
+        self.base_branch.as_deref().unwrap_or("main") // main is default
+    }
+
+    /// Finds the ticket in `branch` using the configured regex.
+    pub fn find_ticket(&self, branch: &str) -> Result<Option<String>, regex::Error> {
+        let Some(pattern) = &self.ticket_regex else {
+            return Ok(None);
+        };
+
+        let captures = Regex::new(pattern)?.captures(branch);
+        Ok(captures.and_then(|captures| {
+            captures
+                .get(1)
+                .or_else(|| captures.get(0))
+                .map(|ticket| ticket.as_str().to_string())
+        }))
+    }
+
+    /// Builds the extra prompt instructions. The conventional commit rules only
+    /// apply to commit messages and pull request titles, the style to everything.
+    pub fn prompt_instructions(&self) -> Instructions {
+        let mut rules = Vec::new();
+
+        if let Some(conventional) = self
+            .conventional_commits
+            .as_ref()
+            .filter(|rules| rules.enabled)
+        {
+            let scope = if conventional.require_scope {
+                "type(scope): subject"
+            } else {
+                "type(scope): subject, where the scope is optional"
+            };
+            rules.push(format!(
+                "Follow the Conventional Commits specification for commit messages and pull request titles, \
+                formatting their first line as {}.",
+                scope
+            ));
+            if !conventional.types.is_empty() {
+                rules.push(format!(
+                    "Only use these types: {}.",
+                    conventional.types.join(", ")
+                ));
+            }
+            if !conventional.scopes.is_empty() {
+                rules.push(format!(
+                    "Only use these scopes: {}.",
+                    conventional.scopes.join(", ")
+                ));
+            }
+        }
+
+        Instructions {
+            message_rules: (!rules.is_empty()).then(|| rules.join("\n")),
+            style: self
+                .prompt_style
+                .as_ref()
+                .map(|style| style.trim().to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_load_repo_reads_every_setting() {
+        let root = tempdir().unwrap();
+        fs::write(
+            root.path().join(REPO_CONFIG_FILE),
+            r#"
+prompt_style = "Write in the imperative mood."
+ticket_regex = "^(?:feature|fix)/([A-Z]+-[0-9]+)"
+ignore = ["*.lock", "vendor/**"]
+base_branch = "develop"
+pr_template = ".github/pull_request_template.md"
+
+[conventional_commits]
+types = ["feat", "fix"]
+require_scope = true
+"#,
+        )
+        .unwrap();
+
+        let settings = Settings::load_repo(root.path()).unwrap().unwrap();
+        assert_eq!(settings.base_branch(), "develop");
+        assert_eq!(settings.ignore, vec!["*.lock", "vendor/**"]);
+        assert_eq!(
+            settings.conventional_commits,
+            Some(ConventionalCommits {
+                enabled: true,
+                types: vec!["feat".to_string(), "fix".to_string()],
+                scopes: Vec::new(),
+                require_scope: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_repo_returns_none_without_file() {
+        let root = tempdir().unwrap();
+
+        assert_eq!(Settings::load_repo(root.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_load_repo_rejects_secrets() {
+        let root = tempdir().unwrap();
+        fs::write(root.path().join(REPO_CONFIG_FILE), "api_token = \"sk-123\"").unwrap();
+
+        assert!(Settings::load_repo(root.path()).is_err());
+    }
+
+    #[test]
+    fn test_merge_prefers_repo_settings() {
+        let user = Settings {
+            prompt_style: Some("Be brief.".to_string()),
+            ignore: vec!["*.lock".to_string()],
+            base_branch: Some("main".to_string()),
+            ..Default::default()
+        };
+        let repo = Settings {
+            ignore: vec!["dist/**".to_string()],
+            base_branch: Some("develop".to_string()),
+            ..Default::default()
+        };
+
+        let settings = user.merge(repo);
+        assert_eq!(settings.prompt_style.as_deref(), Some("Be brief."));
+        assert_eq!(settings.ignore, vec!["*.lock", "dist/**"]);
+        assert_eq!(settings.base_branch(), "develop");
+    }
+
+    #[test]
+    fn test_find_ticket_uses_capture_group() {
+        let settings = Settings {
+            ticket_regex: Some("^feature/([A-Z]+-[0-9]+)".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            settings.find_ticket("feature/ABC-123-login").unwrap(),
+            Some("ABC-123".to_string())
+        );
+        assert_eq!(settings.find_ticket("main").unwrap(), None);
+        assert_eq!(
+            Settings::default().find_ticket("feature/ABC-1").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_prompt_instructions_keeps_rules_apart_from_style() {
+        assert_eq!(
+            Settings::default().prompt_instructions(),
+            Instructions::default()
+        );
+
+        let settings = Settings {
+            prompt_style: Some("Write in English.".to_string()),
+            conventional_commits: Some(ConventionalCommits {
+                types: vec!["feat".to_string(), "fix".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let instructions = settings.prompt_instructions();
+        let rules = instructions.message_rules.unwrap();
+        assert!(rules.contains("Conventional Commits"));
+        assert!(rules.ends_with("Only use these types: feat, fix."));
+        assert_eq!(instructions.style.as_deref(), Some("Write in English."));
+    }
+}
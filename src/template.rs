@@ -0,0 +1,132 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Single-file pull request template locations, in the order GitHub checks them.
+static TEMPLATE_FILES: [&str; 6] = [
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "PULL_REQUEST_TEMPLATE.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+];
+
+/// Directories that may hold several pull request templates.
+static TEMPLATE_DIRS: [&str; 4] = [
+    ".github/PULL_REQUEST_TEMPLATE",
+    ".gitlab/merge_request_templates",
+    "PULL_REQUEST_TEMPLATE",
+    "docs/PULL_REQUEST_TEMPLATE",
+];
+
+/// Returns every pull request template found in the repository at `root`.
+pub fn find_templates(root: &Path) -> Vec<PathBuf> {
+    let mut templates: Vec<PathBuf> = TEMPLATE_FILES
+        .iter()
+        .map(|file| root.join(file))
+        .filter(|path| path.is_file())
+        .collect();
+
+    for dir in TEMPLATE_DIRS {
+        let Ok(entries) = fs::read_dir(root.join(dir)) else {
+            continue;
+        };
+
+        let mut dir_templates: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "md"))
+            .collect();
+        dir_templates.sort();
+        templates.extend(dir_templates);
+    }
+
+    // Case-insensitive filesystems report the same file under several names
+    templates
+        .dedup_by(|a, b| fs::canonicalize(a.as_path()).ok() == fs::canonicalize(b.as_path()).ok());
+
+    templates
+}
+
+/// Resolves a template path from the repository settings. The settings are
+/// committed, so the path must stay inside the repository; otherwise a cloned
+/// repository could have any local file sent to the AI provider.
+pub fn resolve_template_path(root: &Path, path: &str) -> Result<PathBuf, io::Error> {
+    let root = fs::canonicalize(root)?;
+    let resolved = fs::canonicalize(root.join(path))?;
+
+    if resolved.starts_with(&root) {
+        Ok(resolved)
+    } else {
+        Err(io::Error::other(format!(
+            "The pull request template '{}' is outside of the repository",
+            path
+        )))
+    }
+}
+
+pub fn read_template(path: &Path) -> Result<String, io::Error> {
+    fs::read_to_string(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_find_templates_returns_empty_without_templates() {
+        let root = tempdir().unwrap();
+
+        assert!(find_templates(root.path()).is_empty());
+    }
+
+    #[test]
+    fn test_find_templates_finds_single_file_template() {
+        let root = tempdir().unwrap();
+        fs::create_dir_all(root.path().join(".github")).unwrap();
+        fs::write(
+            root.path().join(".github/pull_request_template.md"),
+            "## Summary",
+        )
+        .unwrap();
+
+        let templates = find_templates(root.path());
+        assert_eq!(templates.len(), 1);
+        assert_eq!(read_template(&templates[0]).unwrap(), "## Summary");
+    }
+
+    #[test]
+    fn test_find_templates_lists_template_directory_sorted() {
+        let root = tempdir().unwrap();
+        let dir = root.path().join(".github/PULL_REQUEST_TEMPLATE");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("feature.md"), "## Feature").unwrap();
+        fs::write(dir.join("bugfix.md"), "## Bugfix").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let templates = find_templates(root.path());
+        assert_eq!(
+            templates,
+            vec![dir.join("bugfix.md"), dir.join("feature.md")]
+        );
+    }
+
+    #[test]
+    fn test_resolve_template_path_stays_in_the_repository() {
+        let parent = tempdir().unwrap();
+        let root = parent.path().join("repo");
+        fs::create_dir_all(root.join(".github")).unwrap();
+        fs::write(root.join(".github/template.md"), "## Summary").unwrap();
+        fs::write(parent.path().join("secret"), "key").unwrap();
+
+        assert_eq!(
+            resolve_template_path(&root, ".github/template.md").unwrap(),
+            fs::canonicalize(root.join(".github/template.md")).unwrap()
+        );
+        assert!(resolve_template_path(&root, "../secret").is_err());
+        assert!(resolve_template_path(&root, ".github/../../secret").is_err());
+        let absolute = parent.path().join("secret");
+        assert!(resolve_template_path(&root, absolute.to_str().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Writes the config as TOML while keeping the comments and ordering of the
+//! file the user edited.
+
+use serde::Serialize;
+use std::io;
+use toml_edit::{DocumentMut, Item, Table};
+
+/// Header of newly created config files.
+pub static CONFIG_HEADER: &str = "\
+# gcmgen configuration. Comments and ordering are kept when gcmgen updates this file.
+#
+# default_service        Profile used when no --profile or task profile applies
+# [services.<profile>]   provider = \"OpenAI\" | \"Anthropic\", model, api_token
+#                        api_token is a plaintext string, or one of
+#                        { keyring = \"<account>\" }, { command = \"pass show openai\" },
+#                        { env = \"OPENAI_API_KEY\" }
+# [task_profiles]        commit = \"<profile>\", pr = \"<profile>\"
+# [forges.<name>]        kind = \"Forgejo\" | \"Bitbucket\", base_url, api_token
+# [settings]             Defaults for the keys of a repository's .gcmgen.toml
+
+";
+
+/// Inline tables nested deeper than this stay inline, e.g. `api_token = { env = "..." }`.
+const MAX_TABLE_DEPTH: usize = 2;
+
+/// Serializes `value` as a TOML document, using `[table]` sections for the
+/// top levels instead of inline tables.
+pub fn to_document<T: Serialize>(value: &T) -> Result<DocumentMut, io::Error> {
+    let mut document = toml_edit::ser::to_document(value)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    expand_inline_tables(document.as_table_mut(), 1);
+    Ok(document)
+}
+
+fn expand_inline_tables(table: &mut Table, depth: usize) {
+    if depth > MAX_TABLE_DEPTH {
+        return;
+    }
+
+    for (_, item) in table.iter_mut() {
+        if let Some(inline_table) = item.as_inline_table() {
+            let mut expanded = inline_table.clone().into_table();
+            expand_inline_tables(&mut expanded, depth + 1);
+            // Skip the header of tables that only group other tables, like [services]
+            let only_tables = expanded.iter().all(|(_, item)| item.is_table());
+            expanded.set_implicit(only_tables);
+            *item = Item::Table(expanded);
+        }
+    }
+}
+
+/// Updates `existing` to hold the values of `new`. Keys present in both keep
+/// their comments and position, new keys are appended and missing keys removed.
+pub fn update_document(existing: &mut DocumentMut, new: &DocumentMut) {
+    update_table(existing.as_table_mut(), new.as_table());
+}
+
+fn update_table(existing: &mut Table, new: &Table) {
+    let removed: Vec<String> = existing
+        .iter()
+        .filter(|(key, _)| !new.contains_key(key))
+        .map(|(key, _)| key.to_string())
+        .collect();
+    for key in removed {
+        existing.remove(&key);
+    }
+
+    for (key, new_item) in new.iter() {
+        match existing.get_mut(key) {
+            Some(Item::Table(existing_table)) if new_item.is_table() => {
+                update_table(existing_table, new_item.as_table().unwrap());
+            }
+            Some(Item::Value(existing_value)) if new_item.is_value() => {
+                let mut value = new_item.as_value().unwrap().clone();
+                *value.decor_mut() = existing_value.decor().clone();
+                *existing_value = value;
+            }
+            Some(existing_item) => *existing_item = new_item.clone(),
+            None => {
+                existing.insert(key, new_item.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_document_keeps_comments_and_order() {
+        let mut existing: DocumentMut = r#"# My gcmgen config
+default_service = "OpenAI" # switch with --set-default
+
+# Work account
+[services.work]
+model = "gpt-4o"
+api_token = { env = "WORK_KEY" }
+
+[services.old]
+model = "gpt-3.5-turbo"
+"#
+        .parse()
+        .unwrap();
+        let new: DocumentMut = r#"
+default_service = "work"
+
+[services.work]
+model = "gpt-4.1"
+api_token = { env = "WORK_KEY" }
+
+[task_profiles]
+pr = "work"
+"#
+        .parse()
+        .unwrap();
+
+        update_document(&mut existing, &new);
+
+        assert_eq!(
+            existing.to_string(),
+            r#"# My gcmgen config
+default_service = "work" # switch with --set-default
+
+# Work account
+[services.work]
+model = "gpt-4.1"
+api_token = { env = "WORK_KEY" }
+
+[task_profiles]
+pr = "work"
+"#
+        );
+    }
+}